@@ -2,47 +2,102 @@
 
 export type Procedures = {
     queries: 
+        { key: "maps.list", input: never, result: MapInfo[] } | 
+        { key: "replay.list", input: never, result: string[] } | 
         { key: "version", input: never, result: string },
     mutations: 
         { key: "authentication.login", input: LoginArgs, result: AuthResponse } | 
         { key: "authentication.refresh_token", input: string, result: AuthResponse } | 
         { key: "lobby.action", input: LobbyActionArgs, result: null } | 
-        { key: "lobby.create", input: string[], result: LobbyData } | 
+        { key: "lobby.create", input: LobbyCreateArgs, result: LobbyData } | 
+        { key: "lobby.fire", input: LobbyFireArgs, result: null } | 
         { key: "lobby.input", input: LobbyInputArgs, result: null } | 
+        { key: "lobby.inventory", input: LobbyInventoryArgs, result: null } | 
         { key: "lobby.join", input: string, result: null } | 
-        { key: "lobby.ready", input: string, result: null },
+        { key: "lobby.ready", input: string, result: null } | 
+        { key: "lobby.seat", input: LobbySeatArgs, result: null } | 
+        { key: "replay.speed", input: ReplaySpeedArgs, result: null },
     subscriptions: 
-        { key: "lobby.subscribe", input: [string, string], result: PersonalizedGameData }
+        { key: "lobby.subscribe", input: [string, string], result: PersonalizedGameData } | 
+        { key: "replay.watch", input: ReplayWatchArgs, result: PersonalizedGameData }
 };
 
 export type ActionTrigger = { trigger_type: ActionTriggerType }
 
-export type Coordinates = { x: number; y: number }
+export type Vector = { x: number; y: number }
 
 export type AuthResponse = { access_token: string | null; refresh_token: string | null; success: boolean }
 
 export type LoginArgs = { username: string; password: string }
 
-export type LobbyData = { join_code: string; chat: LobbyChat[] }
+export type LobbyData = { join_code: string; chat: LobbyChat[]; map: string }
 
-export type LobbyInputArgs = { access_token: string; lobby_id: string; r: number; x: number; y: number }
+export type LobbyCreateArgs = { map: string | null }
+
+export type MapInfo = { name: string; width: number; height: number; player_spawns: number }
+
+export type LobbyInputArgs = { access_token: string; lobby_id: string; r: number; x: number; y: number; controls: DriveControls | null }
 
 export type PersonSkin = "Default"
 
-export type LobbyActionArgs = { access_token: string; lobby_id: string; action_id: string }
+export type LobbyActionArgs = { access_token: string; lobby_id: string; action_id: string; client_tick: number | null }
 
-export type PersonalizedGameData = { visible_objects: { [key: string]: OutgoingGameObject } }
+export type PersonalizedGameData = { tick: number; timestamp: number; visible_objects: { [key: string]: OutgoingGameObject }; events: GameEvent[]; kill_feed: KillFeedEntry[]; inventory: Inventory | null; traffic_lights: TrafficLightInfo[] }
 
 export type LobbyChat = { user_id: string; message: string }
 
 export type ActionTriggerType = { ActionKeyPressed: number }
 
-export type CarDetails = { skin: CarSkin; speed: number; acceleration: number; max_passengers: number; passenger_user_ids: string[]; rotation_speed: number; driver_user_id: string | null }
+export type CarDetails = { skin: CarSkin; speed: number; current_speed: number; acceleration: number; max_passengers: number; passenger_user_ids: string[]; rotation_speed: number; driver_user_id: string | null; health: number; max_health: number; damage: DamageState }
 
 export type CarSkin = "Sedan" | "Police"
 
-export type PersonDetails = { user_id: string; skin: PersonSkin }
+export type PersonDetails = { user_id: string; skin: PersonSkin; vehicle_id: string | null; health: number; alive: boolean; weapon: WeaponKind; wanted_level: number; pulling_out_of: string | null; npc: boolean; zone: string | null }
+
+export type DriveControls = { throttle: number; brake: number; steer: number }
+
+export type OutgoingGameObject = { id: string; x: number; y: number; rotation: number; velocity: Vector; angular_velocity: number; owner_user_id: string | null; controller_user_id: string | null; details: GameObjectInfo; action: ActionTrigger | null }
+
+export type GameObjectInfo = { Person: PersonDetails } | { Car: CarDetails } | { Item: ItemDetails }
+
+export type ReplaySpeed = "Paused" | "Normal" | "Double"
+
+export type ReplayWatchArgs = { access_token: string; replay_id: string; speed: ReplaySpeed }
+
+export type ReplaySpeedArgs = { access_token: string; replay_id: string; speed: ReplaySpeed }
+
+export type Seat = "Driver" | "Passenger"
+
+export type LobbySeatArgs = { access_token: string; lobby_id: string; seat: Seat }
+
+export type DamageSource = { Vehicle: { vehicle_id: string } } | "Fall" | { Explosion: { vehicle_id: string } } | { Weapon: { weapon: WeaponKind } }
+
+export type GameEventKind = { Damage: { target: string; amount: number; source: DamageSource; attacker: string | null } } | { Death: { target: string; source: DamageSource; killer: string | null } } | { Respawn: { target: string; x: number; y: number } } | { Explosion: { vehicle_id: string; x: number; y: number; radius: number } } | { Muzzle: { shooter: string; weapon: WeaponKind; x: number; y: number; aim: Vector } } | { Hit: { shooter: string; target: string | null; x: number; y: number } } | { WantedLevel: { target: string; level: number } } | { PullOutStarted: { thief: string; vehicle_id: string; duration: number } } | { PullOutInterrupted: { thief: string; vehicle_id: string } } | { VehicleStolen: { thief: string; vehicle_id: string; owner: VehicleOwner } } | { TriggerEntered: { target: string; trigger: string; mission: string } }
+
+export type GameEvent = { id: number; tick: number; kind: GameEventKind }
+
+export type KillFeedEntry = { tick: number; victim: string; killer: string | null; source: DamageSource }
+
+export type DamageState = "Intact" | "Dented" | "Smoking" | "Burning" | "Wrecked"
+
+export type WeaponKind = "Melee" | "Pistol" | "Rifle"
+
+export type LobbyFireArgs = { access_token: string; lobby_id: string; aim: Vector; client_tick: number | null }
+
+export type ItemKind = { Weapon: WeaponKind } | { Ammo: WeaponKind } | "Medkit" | "Cash"
+
+export type ItemStack = { kind: ItemKind; quantity: number }
+
+export type Inventory = { slots: ItemStack[]; cash: number }
+
+export type ItemDetails = { kind: ItemKind; quantity: number }
+
+export type InventoryAction = { Equip: { weapon: WeaponKind } } | { Use: { slot: number } } | { Drop: { slot: number } }
+
+export type LobbyInventoryArgs = { access_token: string; lobby_id: string; action: InventoryAction }
+
+export type VehicleOwner = { Player: { user_id: string } } | "Npc"
 
-export type OutgoingGameObject = { id: string; x: number; y: number; rotation: number; velocity: Coordinates; owner_user_id: string; controller_user_id: string | null; details: GameObjectInfo; action: ActionTrigger | null }
+export type TrafficLightInfo = { id: string; x: number; y: number; width: number; height: number; north_south: TrafficLightState; east_west: TrafficLightState }
 
-export type GameObjectInfo = { Person: PersonDetails } | { Car: CarDetails }
+export type TrafficLightState = "Green" | "Yellow" | "Red"
@@ -10,8 +10,10 @@ use axum::{
 };
 use rusty::{
     database::create_connection,
+    gangsta::map_registry::MapRegistry,
     http::{context::Ctx, routers::create_router},
     lobby::manager::LobbyManager,
+    replay::manager::ReplayManager,
 };
 // use database::create_connection;
 // use error::{AppError, AppResult};
@@ -40,12 +42,16 @@ async fn create_app() -> axum::Router {
     let allowed_methods = [Method::GET, Method::POST, Method::OPTIONS];
     let pool = create_pool().await;
     let lobby_manager = create_lobby_manager().await;
+    let replay_manager = Arc::new(ReplayManager::from_env());
+    let map_registry = Arc::new(MapRegistry::from_env().expect("Unable to load maps"));
 
     axum::Router::new()
         .route("/", get(|| async { "Hello 'rspc'!" }))
         .nest(
             "/rspc",
-            rspc_axum::endpoint(router, |parts: Parts| Ctx::new(pool, parts, lobby_manager)),
+            rspc_axum::endpoint(router, |parts: Parts| {
+                Ctx::new(pool, parts, lobby_manager, replay_manager, map_registry)
+            }),
         )
         .layer(
             CorsLayer::new()
@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 use tokio::sync::Mutex;
 
-use super::{Game, GameState};
+use super::{input_log::GameCommand, Game, GameState};
 
 #[async_trait::async_trait]
 pub trait Action: Send + Sync + Debug + 'static {
@@ -246,3 +246,24 @@ impl ActionBuilder {
         )
     }
 }
+
+// the action key on a world object queues a GameCommand::Action, what that does
+// depends on the object: get in the car, pick the item up
+pub fn use_object_trigger() -> ActionTrigger {
+    ActionBuilder::new(ActionTriggerType::ActionKeyPressed(32))
+        .closure_action(
+            |state,
+             object_id,
+             user_id|
+             -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> {
+                Box::pin(async move {
+                    state
+                        .lock()
+                        .await
+                        .queue_input(user_id, GameCommand::Action { object_id });
+                    Ok(())
+                })
+            },
+        )
+        .build()
+}
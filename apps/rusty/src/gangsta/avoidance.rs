@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use super::{
+    collision::{Body, Shape},
+    map::pixel_to_tile,
+    traffic_light::{light_stop_distance, Axis, STOP_LOOKAHEAD, STOP_MARGIN},
+    vector::Vector,
+    GameObjectType, GameState,
+};
+
+// how far down the road an AI driver pays attention
+const SENSE_RANGE: f32 = 128.0;
+// extra room on either side of the car's own width that still counts as in the way
+const LANE_MARGIN: f32 = 4.0;
+// nobody gets this close to someone on foot, whatever their driving style
+const PEDESTRIAN_DISTANCE: f32 = 32.0;
+
+// what an AI driver sees, in the car's own frame
+struct Obstacle {
+    // distance from our bumper to the near side of the obstacle
+    gap: f32,
+    pedestrian: bool,
+}
+
+impl GameState {
+    // works out for every AI car how far it can go before it has to stand
+    // still: red lights, the car or person in front of it and junctions that
+    // somebody else is still crossing. also picks up the speed limit.
+    // police units in pursuit are left alone
+    pub(super) fn update_ai_drivers(&mut self) {
+        let mut bodies: Vec<(Body, bool)> = self
+            .players
+            .values()
+            .filter(|player| player.is_alive() && player.vehicle_id.is_none())
+            .map(|player| (player.body(Duration::ZERO), true))
+            .collect();
+        bodies.extend(self.objects.iter().filter_map(|(id, obj)| {
+            let pedestrian = matches!(obj.details, GameObjectType::Pedestrian(_));
+            obj.body(id).map(|body| (body, pedestrian))
+        }));
+
+        let mut stops = vec![];
+        for (id, obj) in self.objects.iter() {
+            let GameObjectType::Car(car) = &obj.details else {
+                continue;
+            };
+            if !car.npc_driver || car.driver_user_id.is_some() {
+                continue;
+            }
+            // a unit in pursuit runs lights, doesn't yield and ignores the limit
+            if self.is_police_unit(id) {
+                stops.push((id.clone(), None, None));
+                continue;
+            }
+
+            let heading = car.heading();
+            let half_length = car.params.length / 2.0;
+            let half_width = car.params.width / 2.0;
+            let following = car.behavior.following_distance();
+
+            let lights =
+                light_stop_distance(&self.traffic_lights, car.position, heading, half_length);
+            let traffic = bodies
+                .iter()
+                .filter(|(body, _)| body.id != *id)
+                .filter_map(|(body, pedestrian)| {
+                    obstacle(
+                        car.position,
+                        heading,
+                        half_length,
+                        half_width,
+                        body,
+                        *pedestrian,
+                    )
+                })
+                .map(|obstacle| {
+                    let distance = if obstacle.pedestrian {
+                        PEDESTRIAN_DISTANCE.max(following)
+                    } else {
+                        following
+                    };
+                    (obstacle.gap - distance).max(0.0)
+                })
+                .min_by(|a, b| a.total_cmp(b));
+            let junction = self.yield_distance(id, car.position, heading, half_length);
+
+            let stop = [lights, traffic, junction]
+                .into_iter()
+                .flatten()
+                .min_by(|a, b| a.total_cmp(b));
+            let limit = self
+                .map
+                .road_type(pixel_to_tile(car.position.to_coordinates()))
+                .map(|road_type| road_type.speed_limit());
+            stops.push((id.clone(), stop, limit));
+        }
+
+        for (id, stop, limit) in stops {
+            if let Some(car) = self.vehicle_mut(&id) {
+                car.stop_distance = stop;
+                car.speed_limit = limit;
+            }
+        }
+    }
+
+    // waits in front of a junction while a car going the other way is still in it
+    fn yield_distance(
+        &self,
+        vehicle_id: &str,
+        position: Vector,
+        heading: Vector,
+        half_length: f32,
+    ) -> Option<f32> {
+        let bumper = position + heading * half_length;
+        let axis = Axis::of(heading);
+
+        self.traffic_lights
+            .iter()
+            .filter(|light| !light.contains(position))
+            .filter(|light| {
+                self.objects.iter().any(|(id, obj)| match &obj.details {
+                    GameObjectType::Car(other) => {
+                        id != vehicle_id
+                            && light.contains(other.position)
+                            && other.speed.abs() > 1.0
+                            && Axis::of(other.heading()) != axis
+                    }
+                    _ => false,
+                })
+            })
+            .filter_map(|light| light.distance_along(bumper, heading, STOP_LOOKAHEAD))
+            .map(|distance| (distance - STOP_MARGIN).max(0.0))
+            .min_by(|a, b| a.total_cmp(b))
+    }
+}
+
+// the body if it's in front of the car and overlaps its lane
+fn obstacle(
+    position: Vector,
+    heading: Vector,
+    half_length: f32,
+    half_width: f32,
+    body: &Body,
+    pedestrian: bool,
+) -> Option<Obstacle> {
+    // other cars are treated as if they were lined up with us
+    let (extent, radius) = match body.shape {
+        Shape::Circle { radius } => (radius, radius),
+        Shape::Box {
+            half_length,
+            half_width,
+        } => (half_length, half_width),
+    };
+
+    let offset = body.position - position;
+    let along = offset.dot(heading);
+    let lateral = (offset - heading * along).length();
+    let gap = along - half_length - extent;
+    if along <= 0.0 || gap > SENSE_RANGE || lateral > half_width + radius + LANE_MARGIN {
+        return None;
+    }
+
+    Some(Obstacle {
+        gap: gap.max(0.0),
+        pedestrian,
+    })
+}
+
+mod test {
+    use crate::gangsta::{
+        collision::{Body, Shape},
+        vector::Vector,
+    };
+
+    use super::obstacle;
+
+    #[test]
+    fn test() {
+        let east = Vector::new(1.0, 0.0);
+        let person = |x: f32, y: f32| Body {
+            id: "person".to_string(),
+            position: Vector::new(x, y),
+            heading: 0.0,
+            velocity: Vector::ZERO,
+            shape: Shape::Circle { radius: 6.0 },
+            mass: 80.0,
+        };
+
+        // 100 ahead, less our half length and their radius
+        let ahead = obstacle(Vector::ZERO, east, 14.0, 7.0, &person(100.0, 2.0), true).unwrap();
+        assert_eq!(ahead.gap, 80.0);
+        assert!(ahead.pedestrian);
+
+        assert!(obstacle(Vector::ZERO, east, 14.0, 7.0, &person(-40.0, 0.0), true).is_none());
+        assert!(obstacle(Vector::ZERO, east, 14.0, 7.0, &person(60.0, 30.0), true).is_none());
+        assert!(obstacle(Vector::ZERO, east, 14.0, 7.0, &person(400.0, 0.0), true).is_none());
+    }
+}
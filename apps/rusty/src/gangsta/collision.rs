@@ -0,0 +1,546 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::{
+    map::{pixel_to_tile, tile_center, Coordinates, Map},
+    vector::Vector,
+};
+
+const GRID_CELL_SIZE: f32 = 64.0;
+const TILE_HALF_SIZE: f32 = 8.0;
+// how much of the closing speed survives a hit between two entities
+const RESTITUTION: f32 = 0.2;
+// resting contact (a car idling against a wall) shouldn't spam events
+const MIN_IMPACT_SPEED: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Circle { radius: f32 },
+    // oriented box, half_length runs along the body's heading
+    Box { half_length: f32, half_width: f32 },
+}
+
+impl Shape {
+    fn bounding_radius(&self) -> f32 {
+        match self {
+            Shape::Circle { radius } => *radius,
+            Shape::Box {
+                half_length,
+                half_width,
+            } => half_length.hypot(*half_width),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Body {
+    pub id: String,
+    pub position: Vector,
+    pub heading: f32,
+    pub velocity: Vector,
+    pub shape: Shape,
+    pub mass: f32,
+}
+
+impl Body {
+    fn inverse_mass(&self) -> f32 {
+        if self.mass > 0.0 {
+            1.0 / self.mass
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CollisionEvent {
+    pub a: String,
+    // None when `a` ran into the map
+    pub b: Option<String>,
+    pub position: Vector,
+    pub impact_speed: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Contact {
+    // points from the first shape towards the second
+    normal: Vector,
+    depth: f32,
+}
+
+fn perpendicular(v: Vector) -> Vector {
+    Vector::new(-v.y, v.x)
+}
+
+fn box_axes(heading: f32) -> (Vector, Vector) {
+    let forward = Vector::from_angle(heading);
+    (forward, perpendicular(forward))
+}
+
+fn box_extent(heading: f32, half_length: f32, half_width: f32, axis: Vector) -> f32 {
+    let (forward, side) = box_axes(heading);
+    half_length * forward.dot(axis).abs() + half_width * side.dot(axis).abs()
+}
+
+fn circle_circle(a: Vector, ra: f32, b: Vector, rb: f32) -> Option<Contact> {
+    let delta = b - a;
+    let distance = delta.length();
+    let depth = ra + rb - distance;
+    if depth <= 0.0 {
+        return None;
+    }
+
+    let normal = if distance > 0.0 {
+        delta * (1.0 / distance)
+    } else {
+        Vector::new(1.0, 0.0)
+    };
+    Some(Contact { normal, depth })
+}
+
+// contact normal points from the box towards the circle
+fn box_circle(
+    center: Vector,
+    heading: f32,
+    half_length: f32,
+    half_width: f32,
+    circle: Vector,
+    radius: f32,
+) -> Option<Contact> {
+    let (forward, side) = box_axes(heading);
+    let local = circle - center;
+    let (lx, ly) = (local.dot(forward), local.dot(side));
+    let (cx, cy) = (
+        lx.clamp(-half_length, half_length),
+        ly.clamp(-half_width, half_width),
+    );
+
+    if cx != lx || cy != ly {
+        let closest = center + forward * cx + side * cy;
+        let delta = circle - closest;
+        let distance = delta.length();
+        if distance >= radius {
+            return None;
+        }
+        return Some(Contact {
+            normal: delta * (1.0 / distance),
+            depth: radius - distance,
+        });
+    }
+
+    // the centre is inside the box, leave through the nearest face
+    let (px, py) = (half_length - lx.abs(), half_width - ly.abs());
+    let sign = |v: f32| if v < 0.0 { -1.0 } else { 1.0 };
+    Some(if px < py {
+        Contact {
+            normal: forward * sign(lx),
+            depth: px + radius,
+        }
+    } else {
+        Contact {
+            normal: side * sign(ly),
+            depth: py + radius,
+        }
+    })
+}
+
+// separating axis test over both boxes' edge normals
+fn box_box(
+    a: Vector,
+    a_heading: f32,
+    a_extents: (f32, f32),
+    b: Vector,
+    b_heading: f32,
+    b_extents: (f32, f32),
+) -> Option<Contact> {
+    let (af, aside) = box_axes(a_heading);
+    let (bf, bside) = box_axes(b_heading);
+    let delta = b - a;
+
+    let mut best: Option<Contact> = None;
+    for axis in [af, aside, bf, bside] {
+        let distance = delta.dot(axis);
+        let depth = box_extent(a_heading, a_extents.0, a_extents.1, axis)
+            + box_extent(b_heading, b_extents.0, b_extents.1, axis)
+            - distance.abs();
+        if depth <= 0.0 {
+            return None;
+        }
+        if best.is_none_or(|contact| depth < contact.depth) {
+            let normal = if distance < 0.0 { axis * -1.0 } else { axis };
+            best = Some(Contact { normal, depth });
+        }
+    }
+
+    best
+}
+
+fn shape_contact(
+    a: Vector,
+    a_heading: f32,
+    a_shape: Shape,
+    b: Vector,
+    b_heading: f32,
+    b_shape: Shape,
+) -> Option<Contact> {
+    match (a_shape, b_shape) {
+        (Shape::Circle { radius: ra }, Shape::Circle { radius: rb }) => circle_circle(a, ra, b, rb),
+        (
+            Shape::Box {
+                half_length,
+                half_width,
+            },
+            Shape::Circle { radius },
+        ) => box_circle(a, a_heading, half_length, half_width, b, radius),
+        (
+            Shape::Circle { radius },
+            Shape::Box {
+                half_length,
+                half_width,
+            },
+        ) => box_circle(b, b_heading, half_length, half_width, a, radius).map(|contact| Contact {
+            normal: contact.normal * -1.0,
+            depth: contact.depth,
+        }),
+        (
+            Shape::Box {
+                half_length: al,
+                half_width: aw,
+            },
+            Shape::Box {
+                half_length: bl,
+                half_width: bw,
+            },
+        ) => box_box(a, a_heading, (al, aw), b, b_heading, (bl, bw)),
+    }
+}
+
+fn contact(a: &Body, b: &Body) -> Option<Contact> {
+    shape_contact(
+        a.position, a.heading, a.shape, b.position, b.heading, b.shape,
+    )
+}
+
+// broad phase: bodies are bucketed by the cells their bounding circle touches,
+// only bodies sharing a cell are tested against each other
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, position: Vector, radius: f32) {
+        let cell = |v: f32| (v / self.cell_size).floor() as i32;
+        for x in cell(position.x - radius)..=cell(position.x + radius) {
+            for y in cell(position.y - radius)..=cell(position.y + radius) {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    // sorted so resolution order doesn't depend on HashMap iteration
+    pub fn candidate_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for indices in self.cells.values() {
+            for (i, a) in indices.iter().enumerate() {
+                for b in &indices[i + 1..] {
+                    pairs.push((*a.min(b), *a.max(b)));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+}
+
+fn resolve_pair(bodies: &mut [Body], i: usize, j: usize) -> Option<CollisionEvent> {
+    let contact = contact(&bodies[i], &bodies[j])?;
+    let (inv_a, inv_b) = (bodies[i].inverse_mass(), bodies[j].inverse_mass());
+    let total = inv_a + inv_b;
+    if total == 0.0 {
+        return None;
+    }
+
+    let normal = contact.normal;
+    bodies[i].position -= normal * (contact.depth * inv_a / total);
+    bodies[j].position += normal * (contact.depth * inv_b / total);
+
+    let closing = (bodies[i].velocity - bodies[j].velocity).dot(normal);
+    if closing <= 0.0 {
+        return None;
+    }
+    let impulse = (1.0 + RESTITUTION) * closing / total;
+    bodies[i].velocity -= normal * (impulse * inv_a);
+    bodies[j].velocity += normal * (impulse * inv_b);
+
+    (closing >= MIN_IMPACT_SPEED).then(|| CollisionEvent {
+        a: bodies[i].id.clone(),
+        b: Some(bodies[j].id.clone()),
+        position: (bodies[i].position + bodies[j].position) * 0.5,
+        impact_speed: closing,
+    })
+}
+
+fn is_blocked(map: &Map, tile: Coordinates) -> bool {
+    map.tile_at(tile)
+        .is_some_and(|tile| tile.tile_type.is_solid())
+}
+
+// blocked tiles are immovable boxes, the body takes the whole correction and
+// loses the part of its velocity heading into the wall
+fn resolve_static(body: &mut Body, map: &Map) -> Option<CollisionEvent> {
+    let radius = body.shape.bounding_radius();
+    let min = pixel_to_tile((body.position - Vector::new(radius, radius)).to_coordinates());
+    let max = pixel_to_tile((body.position + Vector::new(radius, radius)).to_coordinates());
+
+    let mut strongest: Option<CollisionEvent> = None;
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let tile = Coordinates { x, y };
+            if !is_blocked(map, tile) {
+                continue;
+            }
+
+            let tile_shape = Shape::Box {
+                half_length: TILE_HALF_SIZE,
+                half_width: TILE_HALF_SIZE,
+            };
+            let Some(contact) = shape_contact(
+                body.position,
+                body.heading,
+                body.shape,
+                tile_center(tile),
+                0.0,
+                tile_shape,
+            ) else {
+                continue;
+            };
+
+            body.position -= contact.normal * contact.depth;
+            let closing = body.velocity.dot(contact.normal);
+            if closing > 0.0 {
+                body.velocity -= contact.normal * closing;
+            }
+
+            if closing >= MIN_IMPACT_SPEED
+                && strongest
+                    .as_ref()
+                    .is_none_or(|event| closing > event.impact_speed)
+            {
+                strongest = Some(CollisionEvent {
+                    a: body.id.clone(),
+                    b: None,
+                    position: body.position,
+                    impact_speed: closing,
+                });
+            }
+        }
+    }
+
+    strongest
+}
+
+// distance along a ray (`direction` normalised) to where it first enters the
+// body, a ray starting inside the body hits at 0
+pub fn ray_cast(origin: Vector, direction: Vector, max_distance: f32, body: &Body) -> Option<f32> {
+    let distance = match body.shape {
+        Shape::Circle { radius } => {
+            let offset = origin - body.position;
+            let b = offset.dot(direction);
+            let c = offset.dot(offset) - radius * radius;
+            if c > 0.0 && b > 0.0 {
+                return None;
+            }
+            let discriminant = b * b - c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            (-b - discriminant.sqrt()).max(0.0)
+        }
+        Shape::Box {
+            half_length,
+            half_width,
+        } => {
+            // slab test in the box's own frame
+            let (forward, side) = box_axes(body.heading);
+            let offset = origin - body.position;
+            let mut near = 0.0_f32;
+            let mut far = max_distance;
+            for (axis, extent) in [(forward, half_length), (side, half_width)] {
+                let (start, step) = (offset.dot(axis), direction.dot(axis));
+                if step.abs() < f32::EPSILON {
+                    if start.abs() > extent {
+                        return None;
+                    }
+                    continue;
+                }
+                let (t1, t2) = ((-extent - start) / step, (extent - start) / step);
+                near = near.max(t1.min(t2));
+                far = far.min(t1.max(t2));
+                if near > far {
+                    return None;
+                }
+            }
+            near
+        }
+    };
+
+    (distance <= max_distance).then_some(distance)
+}
+
+// distance to the first blocked tile along a ray, walked a couple of pixels at a time
+pub fn ray_blocked(map: &Map, origin: Vector, direction: Vector, max_distance: f32) -> Option<f32> {
+    const STEP: f32 = 2.0;
+    let mut distance = 0.0;
+    while distance <= max_distance {
+        let point = origin + direction * distance;
+        if is_blocked(map, pixel_to_tile(point.to_coordinates())) {
+            return Some(distance);
+        }
+        distance += STEP;
+    }
+
+    None
+}
+
+pub fn resolve(bodies: &mut [Body], map: &Map) -> Vec<CollisionEvent> {
+    let mut grid = SpatialGrid::new(GRID_CELL_SIZE);
+    for (index, body) in bodies.iter().enumerate() {
+        grid.insert(index, body.position, body.shape.bounding_radius());
+    }
+
+    let mut events = vec![];
+    for (i, j) in grid.candidate_pairs() {
+        events.extend(resolve_pair(bodies, i, j));
+    }
+    for body in bodies.iter_mut() {
+        events.extend(resolve_static(body, map));
+    }
+
+    events
+}
+
+mod test {
+    use std::f32::consts::FRAC_PI_2;
+
+    use crate::gangsta::{map::Map, vector::Vector};
+
+    use super::{ray_blocked, ray_cast, resolve, Body, Shape};
+
+    fn car(id: &str, position: Vector, heading: f32, velocity: Vector) -> Body {
+        Body {
+            id: id.to_string(),
+            position,
+            heading,
+            velocity,
+            shape: Shape::Box {
+                half_length: 14.0,
+                half_width: 7.0,
+            },
+            mass: 1200.0,
+        }
+    }
+
+    #[test]
+    fn test() {
+        let mut map = Map::new(50, 30);
+        map.set_building(1, 1);
+
+        // head on, overlapping by 4px
+        let mut bodies = vec![
+            car("a", Vector::new(0.0, 0.0), 0.0, Vector::new(100.0, 0.0)),
+            car("b", Vector::new(24.0, 0.0), 0.0, Vector::new(-100.0, 0.0)),
+        ];
+        let events = resolve(&mut bodies, &map);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].impact_speed, 200.0);
+        assert!(bodies[1].position.x - bodies[0].position.x >= 28.0 - 0.001);
+        assert!(bodies[0].velocity.x < 0.0 && bodies[1].velocity.x > 0.0);
+
+        // a person standing beside a car turned sideways gets shoved, the car barely moves
+        let mut bodies = vec![
+            car("car", Vector::ZERO, FRAC_PI_2, Vector::ZERO),
+            Body {
+                id: "tim".to_string(),
+                position: Vector::new(10.0, 0.0),
+                heading: 0.0,
+                velocity: Vector::ZERO,
+                shape: Shape::Circle { radius: 6.0 },
+                mass: 80.0,
+            },
+        ];
+        let events = resolve(&mut bodies, &map);
+        assert!(events.is_empty());
+        assert!(bodies[1].position.x > 12.5);
+        assert!(bodies[0].position.x.abs() < 0.5);
+
+        // far apart, nothing happens
+        let mut bodies = vec![
+            car("a", Vector::ZERO, 0.0, Vector::ZERO),
+            car("b", Vector::new(200.0, 0.0), 0.0, Vector::ZERO),
+        ];
+        assert!(resolve(&mut bodies, &map).is_empty());
+        assert_eq!(bodies[1].position, Vector::new(200.0, 0.0));
+
+        // walking into a building (tile 1,1 covers 16..32) stops at its edge
+        let mut bodies = vec![Body {
+            id: "tim".to_string(),
+            position: Vector::new(36.0, 24.0),
+            heading: 0.0,
+            velocity: Vector::new(-50.0, 0.0),
+            shape: Shape::Circle { radius: 6.0 },
+            mass: 80.0,
+        }];
+        let events = resolve(&mut bodies, &map);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].b, None);
+        assert_eq!(events[0].impact_speed, 50.0);
+        assert_eq!(bodies[0].position, Vector::new(38.0, 24.0));
+        assert_eq!(bodies[0].velocity, Vector::ZERO);
+    }
+
+    #[test]
+    fn rays() {
+        let right = Vector::new(1.0, 0.0);
+        let car = car("car", Vector::new(100.0, 0.0), FRAC_PI_2, Vector::ZERO);
+        // turned sideways the car is 14px wide along x
+        assert_eq!(ray_cast(Vector::ZERO, right, 200.0, &car), Some(93.0));
+        assert_eq!(ray_cast(Vector::ZERO, right, 50.0, &car), None);
+        assert_eq!(ray_cast(Vector::new(0.0, 20.0), right, 200.0, &car), None);
+
+        let person = Body {
+            id: "tim".to_string(),
+            position: Vector::new(0.0, 50.0),
+            heading: 0.0,
+            velocity: Vector::ZERO,
+            shape: Shape::Circle { radius: 6.0 },
+            mass: 80.0,
+        };
+        assert_eq!(
+            ray_cast(Vector::ZERO, Vector::new(0.0, 1.0), 100.0, &person),
+            Some(44.0)
+        );
+        assert_eq!(
+            ray_cast(Vector::ZERO, Vector::new(0.0, -1.0), 100.0, &person),
+            None
+        );
+
+        let mut map = Map::new(50, 30);
+        map.set_building(4, 1);
+        assert_eq!(
+            ray_blocked(&map, Vector::new(8.0, 24.0), right, 100.0),
+            Some(56.0)
+        );
+        assert_eq!(ray_blocked(&map, Vector::new(8.0, 24.0), right, 50.0), None);
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::VecDeque, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::{vector::Vector, vehicle::VehicleOwner, weapon::WeaponKind};
+
+// long enough that a client on the slowest snapshot rate still sees every event,
+// clients drop the ones they've already handled by id
+pub const EVENT_RETENTION: Duration = Duration::from_secs(1);
+pub const KILL_FEED_LENGTH: usize = 5;
+// events with a position only go to players within this many pixels
+pub const VIEW_DISTANCE: f32 = 640.0;
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DamageSource {
+    Vehicle { vehicle_id: String },
+    Fall,
+    Explosion { vehicle_id: String },
+    Weapon { weapon: WeaponKind },
+}
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum GameEventKind {
+    Damage {
+        target: String,
+        amount: f32,
+        source: DamageSource,
+        attacker: Option<String>,
+    },
+    Death {
+        target: String,
+        source: DamageSource,
+        killer: Option<String>,
+    },
+    Respawn {
+        target: String,
+        x: f32,
+        y: f32,
+    },
+    Explosion {
+        vehicle_id: String,
+        x: f32,
+        y: f32,
+        radius: f32,
+    },
+    Muzzle {
+        shooter: String,
+        weapon: WeaponKind,
+        x: f32,
+        y: f32,
+        aim: Vector,
+    },
+    // target is None when the shot stopped at a wall
+    Hit {
+        shooter: String,
+        target: Option<String>,
+        x: f32,
+        y: f32,
+    },
+    WantedLevel {
+        target: String,
+        level: u8,
+    },
+    // duration in seconds
+    PullOutStarted {
+        thief: String,
+        vehicle_id: String,
+        duration: f32,
+    },
+    PullOutInterrupted {
+        thief: String,
+        vehicle_id: String,
+    },
+    VehicleStolen {
+        thief: String,
+        vehicle_id: String,
+        owner: VehicleOwner,
+    },
+    TriggerEntered {
+        target: String,
+        trigger: String,
+        mission: String,
+    },
+}
+
+impl GameEventKind {
+    pub fn position(&self) -> Option<Vector> {
+        match self {
+            GameEventKind::Explosion { x, y, .. }
+            | GameEventKind::Muzzle { x, y, .. }
+            | GameEventKind::Hit { x, y, .. } => Some(Vector::new(*x, *y)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameEvent {
+    pub id: u32,
+    pub tick: u32,
+    pub kind: GameEventKind,
+}
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KillFeedEntry {
+    pub tick: u32,
+    pub victim: String,
+    // None for deaths nobody caused, like jumping out of a speeding car
+    pub killer: Option<String>,
+    pub source: DamageSource,
+}
+
+#[derive(Debug, Default)]
+pub struct EventLog {
+    next_id: u32,
+    events: VecDeque<(Duration, GameEvent)>,
+    kill_feed: VecDeque<KillFeedEntry>,
+}
+
+impl EventLog {
+    pub fn push(&mut self, tick: u32, time: Duration, kind: GameEventKind) {
+        if let GameEventKind::Death {
+            target,
+            source,
+            killer,
+        } = &kind
+        {
+            self.kill_feed.push_back(KillFeedEntry {
+                tick,
+                victim: target.clone(),
+                killer: killer.clone(),
+                source: source.clone(),
+            });
+            if self.kill_feed.len() > KILL_FEED_LENGTH {
+                self.kill_feed.pop_front();
+            }
+        }
+
+        self.next_id += 1;
+        self.events.push_back((
+            time,
+            GameEvent {
+                id: self.next_id,
+                tick,
+                kind,
+            },
+        ));
+    }
+
+    pub fn prune(&mut self, now: Duration) {
+        let oldest = now.saturating_sub(EVENT_RETENTION);
+        while self.events.front().is_some_and(|(time, _)| *time < oldest) {
+            self.events.pop_front();
+        }
+    }
+
+    pub fn recent(&self) -> impl Iterator<Item = &GameEvent> {
+        self.events.iter().map(|(_, event)| event)
+    }
+
+    // everything without a position plus what happened within VIEW_DISTANCE
+    pub fn visible_from(&self, viewer: Vector) -> impl Iterator<Item = &GameEvent> {
+        self.recent().filter(move |event| {
+            event
+                .kind
+                .position()
+                .is_none_or(|position| (position - viewer).length() <= VIEW_DISTANCE)
+        })
+    }
+
+    pub fn kill_feed(&self) -> impl Iterator<Item = &KillFeedEntry> {
+        self.kill_feed.iter()
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use super::vector::Vector;
+
+pub const HISTORY_LENGTH: Duration = Duration::from_secs(1);
+pub const MAX_REWIND: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntitySnapshot {
+    pub position: Vector,
+    pub rotation: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryFrame {
+    pub tick: u32,
+    pub time: Duration,
+    pub entities: HashMap<String, EntitySnapshot>,
+}
+
+#[derive(Debug, Default)]
+pub struct PositionHistory {
+    frames: VecDeque<HistoryFrame>,
+}
+
+impl PositionHistory {
+    pub fn record(&mut self, frame: HistoryFrame) {
+        let oldest = frame.time.saturating_sub(HISTORY_LENGTH);
+        self.frames.push_back(frame);
+        while self.frames.front().is_some_and(|frame| frame.time < oldest) {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&HistoryFrame> {
+        self.frames.back()
+    }
+
+    // the newest frame at or before `client_tick`, clamped so we never rewind
+    // further than MAX_REWIND behind the latest recorded tick
+    pub fn rewind(&self, client_tick: u32) -> Option<&HistoryFrame> {
+        let latest = self.frames.back()?;
+        let earliest = latest.time.saturating_sub(MAX_REWIND);
+
+        let mut oldest_allowed = latest;
+        for frame in self.frames.iter().rev() {
+            if frame.time < earliest {
+                break;
+            }
+            if frame.tick <= client_tick {
+                return Some(frame);
+            }
+            oldest_allowed = frame;
+        }
+
+        Some(oldest_allowed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+mod test {
+    use std::{collections::HashMap, time::Duration};
+
+    use super::{HistoryFrame, PositionHistory};
+
+    fn frame(tick: u32) -> HistoryFrame {
+        HistoryFrame {
+            tick,
+            time: Duration::from_millis(50) * tick,
+            entities: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test() {
+        let mut history = PositionHistory::default();
+        for tick in 1..=60 {
+            history.record(frame(tick));
+        }
+
+        // one second of 50ms ticks plus the boundary frame
+        assert_eq!(history.len(), 21);
+
+        assert_eq!(history.rewind(55).unwrap().tick, 55);
+        assert_eq!(history.rewind(100).unwrap().tick, 60);
+        // 500ms max rewind from tick 60 is tick 50
+        assert_eq!(history.rewind(10).unwrap().tick, 50);
+    }
+}
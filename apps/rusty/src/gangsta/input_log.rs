@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    inventory::InventoryAction,
+    vector::Vector,
+    vehicle::{DriveControls, Seat},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum GameCommand {
+    Move {
+        x: i32,
+        y: i32,
+        rotation: f32,
+    },
+    Drive(DriveControls),
+    SwitchSeat {
+        seat: Seat,
+    },
+    Action {
+        object_id: String,
+    },
+    Fire {
+        aim: Vector,
+        client_tick: Option<u32>,
+    },
+    Inventory(InventoryAction),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputRecord {
+    pub user_id: String,
+    pub command: GameCommand,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TickInputs {
+    pub tick: u32,
+    pub inputs: Vec<InputRecord>,
+}
+
+// everything needed to re-simulate a match from its seed: the fixed timestep
+// and every command applied, grouped by the tick it was applied on
+// while a match is being recorded the ticks go to the replay file instead
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct InputLog {
+    pub seed: u64,
+    pub timestep: Duration,
+    pub total_ticks: u32,
+    pub ticks: Vec<TickInputs>,
+}
+
+impl InputLog {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    pub fn record(&mut self, tick: u32, dt: Duration, inputs: Vec<InputRecord>) {
+        if self.timestep.is_zero() {
+            self.timestep = dt;
+        } else if self.timestep != dt {
+            eprintln!(
+                "tick {} ran with {:?} instead of {:?}, replays will diverge",
+                tick, dt, self.timestep
+            );
+        }
+
+        self.total_ticks = tick;
+        if !inputs.is_empty() {
+            self.ticks.push(TickInputs { tick, inputs });
+        }
+    }
+
+    pub fn inputs_for(&self, tick: u32) -> &[InputRecord] {
+        self.ticks
+            .binary_search_by_key(&tick, |entry| entry.tick)
+            .map(|index| self.ticks[index].inputs.as_slice())
+            .unwrap_or(&[])
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::weapon::WeaponKind;
+
+pub const INVENTORY_SLOTS: usize = 6;
+const MEDKIT_STACK: u32 = 3;
+const STARTING_AMMO: u32 = 36;
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Weapon(WeaponKind),
+    Ammo(WeaponKind),
+    Medkit,
+    // never takes a slot, goes straight into the wallet
+    Cash,
+}
+
+impl ItemKind {
+    pub fn max_stack(&self) -> u32 {
+        match self {
+            ItemKind::Weapon(_) => 1,
+            ItemKind::Ammo(weapon) => weapon.params().max_ammo.unwrap_or(0),
+            ItemKind::Medkit => MEDKIT_STACK,
+            ItemKind::Cash => u32::MAX,
+        }
+    }
+}
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ItemStack {
+    pub kind: ItemKind,
+    pub quantity: u32,
+}
+
+impl ItemStack {
+    pub fn new(kind: ItemKind, quantity: u32) -> Self {
+        Self { kind, quantity }
+    }
+}
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum InventoryAction {
+    Equip { weapon: WeaponKind },
+    Use { slot: u8 },
+    Drop { slot: u8 },
+}
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Inventory {
+    pub slots: Vec<ItemStack>,
+    pub cash: u32,
+}
+
+impl Inventory {
+    // what every player spawns with
+    pub fn starting() -> Self {
+        let mut inventory = Self::default();
+        inventory.add(ItemStack::new(ItemKind::Weapon(WeaponKind::Pistol), 1));
+        inventory.add(ItemStack::new(
+            ItemKind::Ammo(WeaponKind::Pistol),
+            STARTING_AMMO,
+        ));
+        inventory
+    }
+
+    // tops up existing stacks first, then fills free slots; whatever doesn't
+    // fit is handed back
+    pub fn add(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        if stack.kind == ItemKind::Cash {
+            self.cash = self.cash.saturating_add(stack.quantity);
+            return None;
+        }
+
+        let max = stack.kind.max_stack();
+        let mut remaining = stack.quantity;
+        for slot in self.slots.iter_mut().filter(|slot| slot.kind == stack.kind) {
+            let moved = remaining.min(max.saturating_sub(slot.quantity));
+            slot.quantity += moved;
+            remaining -= moved;
+        }
+        while remaining > 0 && max > 0 && self.slots.len() < INVENTORY_SLOTS {
+            let moved = remaining.min(max);
+            self.slots.push(ItemStack::new(stack.kind, moved));
+            remaining -= moved;
+        }
+
+        (remaining > 0).then(|| ItemStack::new(stack.kind, remaining))
+    }
+
+    pub fn count(&self, kind: ItemKind) -> u32 {
+        if kind == ItemKind::Cash {
+            return self.cash;
+        }
+        self.slots
+            .iter()
+            .filter(|slot| slot.kind == kind)
+            .map(|slot| slot.quantity)
+            .sum()
+    }
+
+    // fists are always at hand
+    pub fn has_weapon(&self, weapon: WeaponKind) -> bool {
+        weapon == WeaponKind::Melee || self.count(ItemKind::Weapon(weapon)) > 0
+    }
+
+    // removes up to `quantity`, emptying the last matching slots first, and
+    // returns how many were taken
+    pub fn take(&mut self, kind: ItemKind, quantity: u32) -> u32 {
+        if kind == ItemKind::Cash {
+            let taken = quantity.min(self.cash);
+            self.cash -= taken;
+            return taken;
+        }
+
+        let mut taken = 0;
+        for slot in self.slots.iter_mut().rev().filter(|slot| slot.kind == kind) {
+            let moved = (quantity - taken).min(slot.quantity);
+            slot.quantity -= moved;
+            taken += moved;
+        }
+        self.slots.retain(|slot| slot.quantity > 0);
+        taken
+    }
+
+    pub fn remove_slot(&mut self, slot: usize) -> Option<ItemStack> {
+        (slot < self.slots.len()).then(|| self.slots.remove(slot))
+    }
+
+    // empties the inventory, cash included
+    pub fn drain(&mut self) -> Vec<ItemStack> {
+        let mut items: Vec<ItemStack> = self.slots.drain(..).collect();
+        if self.cash > 0 {
+            items.push(ItemStack::new(ItemKind::Cash, self.cash));
+            self.cash = 0;
+        }
+        items
+    }
+}
+
+mod test {
+    use crate::gangsta::weapon::WeaponKind;
+
+    use super::{Inventory, ItemKind, ItemStack, INVENTORY_SLOTS};
+
+    #[test]
+    fn test() {
+        let mut inventory = Inventory::starting();
+        assert!(inventory.has_weapon(WeaponKind::Pistol));
+        assert!(!inventory.has_weapon(WeaponKind::Rifle));
+
+        // pistol ammo stacks to 60, the extra spills into a new slot
+        let ammo = ItemKind::Ammo(WeaponKind::Pistol);
+        assert_eq!(inventory.add(ItemStack::new(ammo, 40)), None);
+        assert_eq!(inventory.count(ammo), 76);
+        assert_eq!(inventory.slots.len(), 3);
+
+        assert_eq!(inventory.add(ItemStack::new(ItemKind::Cash, 250)), None);
+        assert_eq!(inventory.cash, 250);
+
+        for _ in 0..INVENTORY_SLOTS {
+            inventory.add(ItemStack::new(ItemKind::Weapon(WeaponKind::Rifle), 1));
+        }
+        assert_eq!(inventory.slots.len(), INVENTORY_SLOTS);
+        let medkits = ItemStack::new(ItemKind::Medkit, 2);
+        assert_eq!(inventory.add(medkits), Some(medkits));
+
+        assert_eq!(inventory.take(ammo, 50), 50);
+        assert_eq!(inventory.count(ammo), 26);
+
+        let dropped = inventory.drain();
+        assert!(dropped.contains(&ItemStack::new(ItemKind::Cash, 250)));
+        assert_eq!(inventory, Inventory::default());
+    }
+}
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use super::{
+    action::use_object_trigger, inventory::ItemStack, vector::Vector, GameObjectInfo, ItemDetails,
+    OutgoingGameObject,
+};
+
+// an item lying on the ground, anyone in range can pick it up with the action key
+#[derive(Debug, Clone)]
+pub struct WorldItem {
+    pub id: String,
+    pub position: Vector,
+    pub stack: ItemStack,
+    // items placed by the map stay, dropped ones eventually disappear
+    pub despawn_at: Option<Duration>,
+}
+
+impl WorldItem {
+    pub fn to_outgoing_game_object(&self) -> OutgoingGameObject {
+        OutgoingGameObject {
+            id: self.id.clone(),
+            x: self.position.x,
+            y: self.position.y,
+            rotation: 0.0,
+            velocity: Vector::ZERO,
+            angular_velocity: 0.0,
+            owner_user_id: None,
+            controller_user_id: None,
+            details: GameObjectInfo::Item(ItemDetails {
+                kind: self.stack.kind,
+                quantity: self.stack.quantity,
+            }),
+            action: Some(use_object_trigger()),
+        }
+    }
+}
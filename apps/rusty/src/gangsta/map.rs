@@ -1,6 +1,5 @@
 use std::{
-    cmp::Reverse,
-    collections::{BinaryHeap, HashMap},
+    collections::{BTreeSet, HashMap},
     error::Error,
     fs::read_to_string,
 };
@@ -8,7 +7,13 @@ use std::{
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
-use super::vehicle::Vehicle;
+use super::{
+    map_features::MapFeatures,
+    pathfinding::JunctionGraph,
+    road_graph::{RoadGraph, RoadRuns},
+    vector::Vector,
+    vehicle::Vehicle,
+};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 pub struct MapConfig {
@@ -31,15 +36,23 @@ pub struct MapConfig {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 pub struct Layer {
+    // tile layers have chunks, object groups have objects
+    #[serde(default)]
     pub chunks: Vec<Chunk>,
+    #[serde(default)]
+    pub objects: Vec<MapObject>,
+    #[serde(default)]
     pub height: usize,
     pub id: usize,
     pub name: String,
     pub opacity: i32,
+    #[serde(default)]
     pub startx: i32,
+    #[serde(default)]
     pub starty: i32,
     pub r#type: String,
     pub visible: bool,
+    #[serde(default)]
     pub width: usize,
     pub x: i32,
     pub y: i32,
@@ -47,10 +60,35 @@ pub struct Layer {
     pub offsety: Option<f64>,
 }
 
+// a point or rectangle placed on an object layer, in Tiled's pixels
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct MapObject {
+    pub id: usize,
+    #[serde(default)]
+    pub name: String,
+    // Tiled 1.9 called this the class
+    #[serde(default, alias = "class")]
+    pub r#type: String,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub width: f32,
+    #[serde(default)]
+    pub height: f32,
+    // degrees clockwise
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default)]
+    pub point: bool,
+    #[serde(default)]
+    pub properties: Vec<Property>,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct Chunk {
-    pub data: Vec<i32>,
+    // gids, with Tiled's flip flags in the top bits
+    pub data: Vec<u32>,
     pub height: usize,
     pub width: usize,
     pub x: i32,
@@ -71,13 +109,106 @@ pub struct Tileset {
     pub tilecount: usize,
     pub tileheight: usize,
     pub tilewidth: usize,
+    // only tiles with properties are listed
+    #[serde(default)]
+    pub tiles: Vec<TileDefinition>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct TileDefinition {
+    pub id: usize,
+    #[serde(default)]
+    pub properties: Vec<Property>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct Property {
+    pub name: String,
+    pub r#type: String,
+    pub value: PropertyValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+pub fn string_property(properties: &[Property], name: &str) -> Result<Option<String>, String> {
+    properties
+        .iter()
+        .find(|property| property.name == name)
+        .map(|property| match &property.value {
+            PropertyValue::String(value) => Ok(value.clone()),
+            value => Err(format!("'{name}' should be a string, got {value:?}")),
+        })
+        .transpose()
+}
+
+pub fn int_property(properties: &[Property], name: &str) -> Result<Option<i64>, String> {
+    properties
+        .iter()
+        .find(|property| property.name == name)
+        .map(|property| match &property.value {
+            PropertyValue::Int(value) => Ok(*value),
+            value => Err(format!("'{name}' should be an int, got {value:?}")),
+        })
+        .transpose()
+}
+
+pub fn bool_property(properties: &[Property], name: &str) -> Result<Option<bool>, String> {
+    properties
+        .iter()
+        .find(|property| property.name == name)
+        .map(|property| match &property.value {
+            PropertyValue::Bool(value) => Ok(*value),
+            value => Err(format!("'{name}' should be a bool, got {value:?}")),
+        })
+        .transpose()
+}
+
+// the top bits of a gid say how the tile is flipped or rotated
+const GID_FLAGS: u32 = 0xF000_0000;
+
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum TileType {
     Empty,
     Road(RoadType),
     Building,
+    // walls, fences and the like, solid but not a building
+    Obstacle,
+}
+
+impl TileType {
+    pub fn is_solid(&self) -> bool {
+        matches!(self, TileType::Building | TileType::Obstacle)
+    }
+
+    // from the `tile` and `road_type` properties of a tileset tile
+    fn from_properties(properties: &[Property]) -> Result<Option<TileType>, String> {
+        let property = |name: &str| {
+            string_property(properties, name).map(|value| value.map(|value| value.to_lowercase()))
+        };
+
+        let road_type = match property("road_type")?.as_deref() {
+            None | Some("local") => RoadType::Local,
+            Some("collector") => RoadType::Collector,
+            Some("arterial") => RoadType::Arterial,
+            Some("interstate") => RoadType::Interstate,
+            Some(other) => return Err(format!("unknown road_type '{other}'")),
+        };
+        match property("tile")?.as_deref() {
+            None => Ok(None),
+            Some("empty") => Ok(Some(TileType::Empty)),
+            Some("road") => Ok(Some(TileType::Road(road_type))),
+            Some("building") => Ok(Some(TileType::Building)),
+            Some("obstacle") => Ok(Some(TileType::Obstacle)),
+            Some(other) => Err(format!("unknown tile type '{other}'")),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -99,27 +230,91 @@ pub enum RoadType {
     Local,
 }
 
-#[derive(Debug)]
-pub struct Road {
-    pub start: Coordinates,
-    pub end: Coordinates,
-    pub cost: i32,
-    pub road_type: RoadType,
+impl RoadType {
+    // what driving across one tile costs the pathfinder, bigger roads are cheaper
+    pub fn cost(&self) -> i32 {
+        match self {
+            RoadType::Interstate => 1,
+            RoadType::Arterial => 2,
+            RoadType::Collector => 3,
+            RoadType::Local => 4,
+        }
+    }
+
+    // pixels per second
+    pub fn speed_limit(&self) -> f32 {
+        match self {
+            RoadType::Interstate => 320.0,
+            RoadType::Arterial => 240.0,
+            RoadType::Collector => 180.0,
+            RoadType::Local => 140.0,
+        }
+    }
+}
+
+// a block of road tiles shared by three or more roads, min and max inclusive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Intersection {
+    pub min: Coordinates,
+    pub max: Coordinates,
+}
+
+impl Intersection {
+    pub fn center(&self) -> Vector {
+        (tile_center(self.min) + tile_center(self.max)) * 0.5
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Map {
+    // the file it was loaded from, without the extension
+    pub name: String,
     width: usize,
     height: usize,
     pub grid: Vec<Vec<Tile>>,
+    roads: RoadGraph,
+    junctions: JunctionGraph,
+    features: MapFeatures,
 }
 
 impl Map {
+    // tiles take their type from the tileset properties, the topmost layer
+    // that gives a tile a type wins. tiles without a `tile` property are
+    // local roads on the "road" layer and scenery anywhere else
     pub fn from_json(str: &str) -> Result<Map, Box<dyn std::error::Error>> {
         let tiled_map: MapConfig = serde_json::from_str(str)?;
-        let road_layer = tiled_map
+        let mut problems = vec![];
+
+        let mut tile_types: HashMap<u32, TileType> = HashMap::new();
+        for tileset in &tiled_map.tilesets {
+            for tile in &tileset.tiles {
+                let gid = (tileset.firstgid + tile.id) as u32;
+                match TileType::from_properties(&tile.properties) {
+                    Ok(Some(tile_type)) => {
+                        tile_types.insert(gid, tile_type);
+                    }
+                    Ok(None) => {}
+                    Err(error) => {
+                        problems.push(format!("tile {gid} in '{}': {error}", tileset.name))
+                    }
+                }
+            }
+        }
+        let is_known = |gid: u32| {
+            tiled_map.tilesets.iter().any(|tileset| {
+                let first = tileset.firstgid as u32;
+                gid >= first && gid < first + tileset.tilecount as u32
+            })
+        };
+
+        let layers: Vec<&Layer> = tiled_map
             .layers
-            .into_iter()
+            .iter()
+            .filter(|layer| layer.r#type == "tilelayer")
+            .collect();
+        // the grid covers the road layer, the other layers are cut to fit
+        let road_layer = layers
+            .iter()
             .find(|layer| layer.name.to_lowercase() == "road")
             .ok_or("No 'road' layer found in the map")?;
         let mut min_x: isize = isize::MAX;
@@ -148,44 +343,261 @@ impl Map {
         let grid_height = (max_y - min_y) as usize;
         let mut grid: Vec<Vec<Tile>> =
             vec![vec![Tile::new(TileType::Empty); grid_width]; grid_height];
-        for chunk in &road_layer.chunks {
-            for (index, &tile_id) in chunk.data.iter().enumerate() {
-                let x = chunk.x as isize + (index % chunk.width) as isize;
-                let y = chunk.y as isize + (index / chunk.width) as isize;
-                let grid_x = (x - min_x) as usize;
-                let grid_y = (y - min_y) as usize;
-                let tile = if tile_id == 0 {
-                    Tile::new(TileType::Empty)
-                } else {
-                    Tile::new(TileType::Road(RoadType::Local))
-                };
-                grid[grid_y][grid_x] = tile;
+        for layer in layers {
+            let is_road_layer = layer.name.to_lowercase() == "road";
+            let mut unknown = BTreeSet::new();
+            for chunk in &layer.chunks {
+                for (index, &tile_id) in chunk.data.iter().enumerate() {
+                    let gid = tile_id & !GID_FLAGS;
+                    if gid == 0 {
+                        continue;
+                    }
+                    if !is_known(gid) {
+                        unknown.insert(gid);
+                        continue;
+                    }
+                    let tile_type = match tile_types.get(&gid) {
+                        Some(tile_type) => *tile_type,
+                        None if is_road_layer => TileType::Road(RoadType::Local),
+                        None => continue,
+                    };
+                    let x = chunk.x as isize + (index % chunk.width) as isize;
+                    let y = chunk.y as isize + (index / chunk.width) as isize;
+                    if x < min_x || y < min_y || x >= max_x || y >= max_y {
+                        continue;
+                    }
+                    let grid_x = (x - min_x) as usize;
+                    let grid_y = (y - min_y) as usize;
+                    grid[grid_y][grid_x] = Tile::new(tile_type);
+                }
+            }
+            if !unknown.is_empty() {
+                let gids: Vec<String> = unknown.iter().map(|gid| gid.to_string()).collect();
+                problems.push(format!(
+                    "unknown tile gids in layer '{}': {}",
+                    layer.name,
+                    gids.join(", ")
+                ));
             }
         }
-        Ok(Map {
+        let origin = Vector::new(
+            (min_x * tiled_map.tilewidth as isize) as f32,
+            (min_y * tiled_map.tileheight as isize) as f32,
+        );
+        let features = match MapFeatures::from_layers(&tiled_map.layers, origin) {
+            Ok(features) => features,
+            Err(errors) => {
+                problems.extend(errors);
+                MapFeatures::default()
+            }
+        };
+        if !problems.is_empty() {
+            return Err(problems.join("; ").into());
+        }
+
+        let mut map = Map {
+            name: String::new(),
             width: grid_width,
             height: grid_height,
             grid,
-        })
+            roads: RoadGraph::default(),
+            junctions: JunctionGraph::default(),
+            features,
+        };
+        map.rebuild_roads();
+
+        Ok(map)
     }
 
     pub fn new(width: usize, height: usize) -> Self {
         let grid = vec![vec![Tile::new(TileType::Empty); width]; height];
         let mut map = Map {
+            name: String::new(),
             width,
             height,
             grid,
+            roads: RoadGraph::default(),
+            junctions: JunctionGraph::default(),
+            features: MapFeatures::default(),
         };
 
         map.generate();
+        map.rebuild_roads();
 
         map
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn roads(&self) -> &RoadGraph {
+        &self.roads
+    }
+
+    pub fn junctions(&self) -> &JunctionGraph {
+        &self.junctions
+    }
+
+    pub fn features(&self) -> &MapFeatures {
+        &self.features
+    }
+
+    // in pixels
+    pub fn center(&self) -> Vector {
+        tile_to_pixel(Coordinates {
+            x: self.width as i32 / 2,
+            y: self.height as i32 / 2,
+        })
+        .into()
+    }
+
+    // the junction graph is built on top of the road graph, so it goes second
+    fn rebuild_roads(&mut self) {
+        self.roads = RoadGraph::new(self);
+        self.junctions = JunctionGraph::new(self);
+    }
+
+    pub fn tile_at(&self, tile: Coordinates) -> Option<&Tile> {
+        if tile.x < 0 || tile.y < 0 {
+            return None;
+        }
+        self.grid.get(tile.y as usize)?.get(tile.x as usize)
+    }
+
+    pub fn is_walkable(&self, tile: Coordinates) -> bool {
+        match self.tile_at(tile) {
+            Some(tile) => !tile.tile_type.is_solid(),
+            None => false,
+        }
+    }
+
+    pub fn is_road(&self, tile: Coordinates) -> bool {
+        self.tile_at(tile)
+            .is_some_and(|tile| matches!(tile.tile_type, TileType::Road(_)))
+    }
+
+    pub fn road_type(&self, tile: Coordinates) -> Option<RoadType> {
+        match self.tile_at(tile)?.tile_type {
+            TileType::Road(road_type) => Some(road_type),
+            _ => None,
+        }
+    }
+
+    // closest road tile by ring distance, searching at most `radius` tiles out
+    pub fn nearest_road(&self, tile: Coordinates, radius: i32) -> Option<Coordinates> {
+        self.nearest(tile, radius, |candidate| self.is_road(candidate))
+    }
+
+    pub fn nearest_sidewalk(&self, tile: Coordinates, radius: i32) -> Option<Coordinates> {
+        self.nearest(tile, radius, |candidate| self.is_sidewalk(candidate))
+    }
+
+    fn nearest(
+        &self,
+        tile: Coordinates,
+        radius: i32,
+        matches: impl Fn(Coordinates) -> bool,
+    ) -> Option<Coordinates> {
+        for ring in 0..=radius {
+            for dy in -ring..=ring {
+                for dx in -ring..=ring {
+                    if dx.abs() != ring && dy.abs() != ring {
+                        continue;
+                    }
+                    let candidate = Coordinates {
+                        x: tile.x + dx,
+                        y: tile.y + dy,
+                    };
+                    if matches(candidate) {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn intersections(&self) -> Vec<Intersection> {
+        let runs = RoadRuns::new(self);
+        let junction = |x: usize, y: usize| runs.is_junction(x, y);
+
+        let mut seen = vec![vec![false; self.width]; self.height];
+        let mut intersections = vec![];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if seen[y][x] || !junction(x, y) {
+                    continue;
+                }
+
+                let (mut min, mut max) = ((x, y), (x, y));
+                let mut stack = vec![(x, y)];
+                seen[y][x] = true;
+                while let Some((cx, cy)) = stack.pop() {
+                    min = (min.0.min(cx), min.1.min(cy));
+                    max = (max.0.max(cx), max.1.max(cy));
+                    let neighbours = [
+                        (cx.wrapping_sub(1), cy),
+                        (cx + 1, cy),
+                        (cx, cy.wrapping_sub(1)),
+                        (cx, cy + 1),
+                    ];
+                    for (nx, ny) in neighbours {
+                        if nx < self.width && ny < self.height && !seen[ny][nx] && junction(nx, ny)
+                        {
+                            seen[ny][nx] = true;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+
+                let intersection = Intersection {
+                    min: Coordinates {
+                        x: min.0 as i32,
+                        y: min.1 as i32,
+                    },
+                    max: Coordinates {
+                        x: max.0 as i32,
+                        y: max.1 as i32,
+                    },
+                };
+                // a bend in the road only has two
+                if self.arms(&intersection) >= 3 {
+                    intersections.push(intersection);
+                }
+            }
+        }
+
+        intersections
+    }
+
+    // how many sides of the block the road carries on from
+    fn arms(&self, intersection: &Intersection) -> usize {
+        let Intersection { min, max } = *intersection;
+        let column = |x: i32| (min.y..=max.y).any(|y| self.is_road(Coordinates { x, y }));
+        let row = |y: i32| (min.x..=max.x).any(|x| self.is_road(Coordinates { x, y }));
+
+        [
+            row(min.y - 1),
+            row(max.y + 1),
+            column(min.x - 1),
+            column(max.x + 1),
+        ]
+        .into_iter()
+        .filter(|arm| *arm)
+        .count()
+    }
+
     pub fn set_road(&mut self, x: usize, y: usize, road_type: RoadType) {
         if x < self.width && y < self.height {
             if let TileType::Empty = self.grid[y][x].tile_type {
                 self.grid[y][x] = Tile::new(TileType::Road(road_type));
+                self.rebuild_roads();
             }
         }
     }
@@ -194,6 +606,7 @@ impl Map {
         if x < self.width && y < self.height {
             if let TileType::Empty = self.grid[y][x].tile_type {
                 self.grid[y][x] = Tile::new(TileType::Building);
+                self.rebuild_roads();
             }
         }
     }
@@ -208,7 +621,7 @@ impl Map {
                     TileType::Road(RoadType::Arterial) => '□',
                     TileType::Road(RoadType::Collector) => '□',
                     TileType::Road(RoadType::Local) => '□',
-                    TileType::Building => ' ',
+                    TileType::Building | TileType::Obstacle => ' ',
                 };
                 line.push(ch);
             }
@@ -217,10 +630,11 @@ impl Map {
     }
 
     pub fn display(&self, vehicle: &Vehicle) {
+        let vehicle_tile = pixel_to_tile(vehicle.position.to_coordinates());
         for y in (0..self.height) {
             let mut line = String::new();
             for x in 0..self.width {
-                if vehicle.position.x as usize == x && vehicle.position.y as usize == y {
+                if vehicle_tile.x as usize == x && vehicle_tile.y as usize == y {
                     line.push('V');
                 } else {
                     let ch = match self.grid[y][x].tile_type {
@@ -229,7 +643,7 @@ impl Map {
                         TileType::Road(RoadType::Arterial) => '□',
                         TileType::Road(RoadType::Collector) => '□',
                         TileType::Road(RoadType::Local) => '□',
-                        TileType::Building => ' ',
+                        TileType::Building | TileType::Obstacle => ' ',
                     };
                     line.push(ch);
                 }
@@ -237,54 +651,6 @@ impl Map {
             println!("{} {}", y, line);
         }
     }
-
-    pub fn find_path(&self, start: Coordinates, goal: Coordinates) -> Option<Vec<Coordinates>> {
-        let mut dist: HashMap<Coordinates, i32> = HashMap::new();
-        let mut prev: HashMap<Coordinates, Coordinates> = HashMap::new();
-        let mut heap = BinaryHeap::new();
-
-        dist.insert(start, 0);
-        heap.push(Reverse((0, start)));
-
-        while let Some(Reverse((cost, pos))) = heap.pop() {
-            if pos == goal {
-                let mut path = Vec::new();
-                let mut current = pos;
-                while let Some(&p) = prev.get(&current) {
-                    path.push(current);
-                    current = p;
-                }
-                path.push(start);
-                path.reverse();
-                return Some(path);
-            }
-
-            for (dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
-                let nx = pos.x + dx;
-                let ny = pos.y + dy;
-
-                if nx < 0 || ny < 0 || nx >= self.width as i32 || ny >= self.height as i32 {
-                    continue;
-                }
-
-                let neighbor = Coordinates { x: nx, y: ny };
-
-                match self.grid[neighbor.y as usize][neighbor.x as usize].tile_type {
-                    TileType::Road(_) => {}
-                    _ => continue,
-                }
-
-                let next_cost = cost + 1;
-                if next_cost < *dist.get(&neighbor).unwrap_or(&i32::MAX) {
-                    dist.insert(neighbor, next_cost);
-                    prev.insert(neighbor, pos);
-                    heap.push(Reverse((next_cost, neighbor)));
-                }
-            }
-        }
-
-        None
-    }
 }
 
 impl Map {
@@ -364,21 +730,96 @@ pub fn tile_to_pixel(tile: Coordinates) -> Coordinates {
     }
 }
 
+pub fn tile_center(tile: Coordinates) -> Vector {
+    Vector::from(tile_to_pixel(tile)) + Vector::new(8.0, 8.0)
+}
+
 mod test {
-    use crate::gangsta::vehicle::Vehicle;
+    use serde_json::json;
+
+    use crate::gangsta::{map_registry::MapRegistry, vehicle::Vehicle};
 
-    use super::Map;
+    use super::{Coordinates, Map, RoadType, TileType};
 
     #[test]
     fn test() {
         let vehicle = Vehicle::new(
             "x".to_string(),
-            super::Coordinates { x: 2, y: 2 },
+            super::tile_center(super::Coordinates { x: 2, y: 2 }),
             crate::gangsta::vehicle::VehicleBehavior::Aggressive,
-            3,
+            crate::gangsta::CarSkin::Sedan,
         );
-        let map =
-            Map::from_json(include_str!("maps/suburb.json")).expect("unable to read json map?");
+        let map = MapRegistry::bundled().default_map().unwrap();
         map.display(&vehicle);
     }
+
+    #[test]
+    fn intersections() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let intersections = map.intersections();
+        // three way junctions along the main road and the loop in the south east,
+        // the corners don't count
+        assert_eq!(intersections.len(), 6);
+        for intersection in intersections {
+            assert!(map.is_road(intersection.min) && map.is_road(intersection.max));
+        }
+    }
+
+    // a 4x1 map with the given layers over one 8 tile tileset
+    fn tiled(layers: &[(&str, [u32; 4])]) -> String {
+        let layers: Vec<_> = layers
+            .iter()
+            .enumerate()
+            .map(|(id, (name, data))| {
+                json!({
+                    "chunks": [{ "data": data, "height": 1, "width": 4, "x": 0, "y": 0 }],
+                    "height": 1, "id": id, "name": name, "opacity": 1, "startx": 0,
+                    "starty": 0, "type": "tilelayer", "visible": true, "width": 4, "x": 0, "y": 0
+                })
+            })
+            .collect();
+        let property =
+            |name: &str, value: &str| json!({ "name": name, "type": "string", "value": value });
+        json!({
+            "compressionlevel": -1, "height": 1, "infinite": true, "layers": layers,
+            "nextlayerid": 3, "nextobjectid": 1, "orientation": "orthogonal",
+            "renderorder": "right-down", "tiledversion": "1.11.2", "tileheight": 16,
+            "tilesets": [{
+                "columns": 8, "firstgid": 1, "image": "tiles.png", "imageheight": 16,
+                "imagewidth": 128, "margin": 0, "name": "tiles", "spacing": 0,
+                "tilecount": 8, "tileheight": 16, "tilewidth": 16,
+                "tiles": [
+                    { "id": 0, "properties": [property("tile", "road"), property("road_type", "arterial")] },
+                    { "id": 1, "properties": [property("tile", "building")] },
+                    { "id": 2, "properties": [property("tile", "obstacle")] },
+                ]
+            }],
+            "tilewidth": 16, "type": "map", "version": "1.10", "width": 4
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn tile_properties() {
+        let map =
+            Map::from_json(&tiled(&[("ground", [2, 3, 8, 0]), ("road", [0, 0, 8, 1])])).unwrap();
+        let tile_type = |x| map.tile_at(Coordinates { x, y: 0 }).unwrap().tile_type;
+        assert_eq!(tile_type(0), TileType::Building);
+        assert_eq!(tile_type(1), TileType::Obstacle);
+        // plain tiles on the road layer are still local roads
+        assert_eq!(tile_type(2), TileType::Road(RoadType::Local));
+        assert_eq!(tile_type(3), TileType::Road(RoadType::Arterial));
+        assert!(!map.is_walkable(Coordinates { x: 1, y: 0 }));
+
+        // gid 9 is past the end of the tileset, flipped tiles are fine
+        let error = Map::from_json(&tiled(&[
+            ("ground", [9, 0, 0, 12]),
+            ("road", [1, 0x8000_0001, 0, 0]),
+        ]))
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown tile gids in layer 'ground': 9, 12"
+        );
+    }
 }
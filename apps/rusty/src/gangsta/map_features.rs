@@ -0,0 +1,329 @@
+use std::collections::BTreeSet;
+
+use super::{
+    events::GameEventKind,
+    inventory::{ItemKind, ItemStack},
+    map::{bool_property, int_property, string_property, Layer, MapObject},
+    vector::Vector,
+    weapon::WeaponKind,
+    CarSkin, GameState,
+};
+
+// a named rectangle on the map, in the same pixels as everything else
+#[derive(Debug, Clone, PartialEq)]
+pub struct Area {
+    pub name: String,
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl Area {
+    pub fn contains(&self, point: Vector) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VehicleSpawn {
+    // the object's name, when it has one, the car's id is made from it
+    pub name: Option<String>,
+    pub position: Vector,
+    pub heading: f32,
+    pub skin: CarSkin,
+    pub owner: Option<String>,
+    // parked cars stay put, the rest get an NPC driver and join the traffic
+    pub parked: bool,
+}
+
+// lying on the ground when the match starts, never despawns
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemSpawn {
+    pub position: Vector,
+    pub stack: ItemStack,
+}
+
+// walking into one starts the mission it names
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub area: Area,
+    pub mission: String,
+}
+
+// everything placed on the map's object layers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapFeatures {
+    pub player_spawns: Vec<Vector>,
+    pub vehicle_spawns: Vec<VehicleSpawn>,
+    pub item_spawns: Vec<ItemSpawn>,
+    pub zones: Vec<Area>,
+    pub triggers: Vec<Trigger>,
+}
+
+impl MapFeatures {
+    // `origin` is where the tile grid starts in Tiled's pixels, objects are
+    // moved by it so they line up with the tiles. untyped objects are notes
+    // for whoever edits the map and are skipped
+    pub fn from_layers(layers: &[Layer], origin: Vector) -> Result<Self, Vec<String>> {
+        let mut features = MapFeatures::default();
+        let mut problems = vec![];
+        for layer in layers.iter().filter(|layer| layer.r#type == "objectgroup") {
+            for object in &layer.objects {
+                if let Err(error) = features.add(object, origin) {
+                    problems.push(format!(
+                        "object {} in layer '{}': {error}",
+                        object.id, layer.name
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(features)
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn zone_at(&self, point: Vector) -> Option<&Area> {
+        self.zones.iter().find(|zone| zone.contains(point))
+    }
+
+    // named map vehicles get ids that can't clash with generated ones
+    pub fn vehicle_id(name: &str) -> String {
+        format!("map-{name}")
+    }
+
+    fn add(&mut self, object: &MapObject, origin: Vector) -> Result<(), String> {
+        let position = Vector::new(object.x, object.y) - origin;
+        let area = || Area {
+            name: object.name.clone(),
+            min: position,
+            max: position + Vector::new(object.width, object.height),
+        };
+
+        match object.r#type.as_str() {
+            "" => {}
+            "player_spawn" => self.player_spawns.push(position),
+            "vehicle_spawn" => {
+                let skin = match string_property(&object.properties, "skin")?.as_deref() {
+                    None | Some("sedan") => CarSkin::Sedan,
+                    Some("police") => CarSkin::Police,
+                    Some(other) => return Err(format!("unknown skin '{other}'")),
+                };
+                let name = (!object.name.is_empty()).then(|| object.name.clone());
+                if name.is_some() && self.vehicle_spawns.iter().any(|spawn| spawn.name == name) {
+                    return Err(format!("duplicate vehicle name '{}'", object.name));
+                }
+                self.vehicle_spawns.push(VehicleSpawn {
+                    name,
+                    position,
+                    heading: object.rotation.to_radians(),
+                    skin,
+                    owner: string_property(&object.properties, "owner")?,
+                    parked: bool_property(&object.properties, "parked")?.unwrap_or(true),
+                });
+            }
+            "item_spawn" => {
+                let quantity = int_property(&object.properties, "quantity")?.unwrap_or(1);
+                let quantity = u32::try_from(quantity)
+                    .ok()
+                    .filter(|quantity| *quantity > 0)
+                    .ok_or(format!("bad quantity {quantity}"))?;
+                self.item_spawns.push(ItemSpawn {
+                    position,
+                    stack: ItemStack::new(item_kind(object)?, quantity),
+                });
+            }
+            "zone" => self.zones.push(area()),
+            "trigger" => {
+                let mission = string_property(&object.properties, "mission")?
+                    .ok_or("trigger without a mission")?;
+                self.triggers.push(Trigger {
+                    area: area(),
+                    mission,
+                });
+            }
+            other => return Err(format!("unknown object type '{other}'")),
+        }
+
+        Ok(())
+    }
+}
+
+// "item" is a weapon, "ammo" with a "weapon" to say which, "medkit" or "cash"
+fn item_kind(object: &MapObject) -> Result<ItemKind, String> {
+    let weapon = |name: &str| match name {
+        "pistol" => Ok(WeaponKind::Pistol),
+        "rifle" => Ok(WeaponKind::Rifle),
+        other => Err(format!("unknown weapon '{other}'")),
+    };
+    match string_property(&object.properties, "item")?.as_deref() {
+        None => Err("item spawn without an item".to_string()),
+        Some("medkit") => Ok(ItemKind::Medkit),
+        Some("cash") => Ok(ItemKind::Cash),
+        Some("ammo") => {
+            let name =
+                string_property(&object.properties, "weapon")?.ok_or("ammo without a weapon")?;
+            Ok(ItemKind::Ammo(weapon(&name)?))
+        }
+        Some(other) => Ok(ItemKind::Weapon(weapon(other)?)),
+    }
+}
+
+impl GameState {
+    // keeps track of the zone each player is in and lets everyone know when
+    // a player walks or drives into a trigger, once per visit
+    pub(super) fn update_triggers(&mut self) {
+        let mut entered = vec![];
+        for player in self.players.values_mut() {
+            player.zone = self
+                .map
+                .features()
+                .zone_at(player.position())
+                .map(|zone| zone.name.clone());
+            let inside: BTreeSet<usize> = if player.is_alive() {
+                let position = player.position();
+                self.map
+                    .features()
+                    .triggers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, trigger)| trigger.area.contains(position))
+                    .map(|(index, _)| index)
+                    .collect()
+            } else {
+                BTreeSet::new()
+            };
+            for index in inside.difference(&player.triggers) {
+                entered.push((player.id.clone(), *index));
+            }
+            player.triggers = inside;
+        }
+
+        for (user_id, index) in entered {
+            let trigger = &self.map.features().triggers[index];
+            let kind = GameEventKind::TriggerEntered {
+                target: user_id,
+                trigger: trigger.area.name.clone(),
+                mission: trigger.mission.clone(),
+            };
+            self.events.push(self.tick, self.time, kind);
+        }
+    }
+}
+
+mod test {
+    use crate::gangsta::{
+        inventory::{ItemKind, ItemStack},
+        map::{Layer, MapObject, Property, PropertyValue},
+        vector::Vector,
+        weapon::WeaponKind,
+        CarSkin,
+    };
+
+    use super::MapFeatures;
+
+    #[test]
+    fn test() {
+        let property = |name: &str, value: PropertyValue| Property {
+            name: name.to_string(),
+            r#type: "string".to_string(),
+            value,
+        };
+        let object = |id, r#type: &str, x, y| MapObject {
+            id,
+            r#type: r#type.to_string(),
+            x,
+            y,
+            ..Default::default()
+        };
+        let mut layer = Layer {
+            name: "features".to_string(),
+            r#type: "objectgroup".to_string(),
+            objects: vec![
+                object(1, "player_spawn", 100.0, 50.0),
+                MapObject {
+                    name: "cop car".to_string(),
+                    rotation: 90.0,
+                    properties: vec![
+                        property("skin", PropertyValue::String("police".to_string())),
+                        property("parked", PropertyValue::Bool(false)),
+                    ],
+                    ..object(2, "vehicle_spawn", 200.0, 50.0)
+                },
+                MapObject {
+                    name: "bank".to_string(),
+                    width: 64.0,
+                    height: 32.0,
+                    properties: vec![property(
+                        "mission",
+                        PropertyValue::String("heist".to_string()),
+                    )],
+                    ..object(3, "trigger", 0.0, 0.0)
+                },
+                object(4, "", 0.0, 0.0),
+                MapObject {
+                    properties: vec![
+                        property("item", PropertyValue::String("ammo".to_string())),
+                        property("weapon", PropertyValue::String("rifle".to_string())),
+                        property("quantity", PropertyValue::Int(90)),
+                    ],
+                    ..object(6, "item_spawn", 32.0, 64.0)
+                },
+            ],
+            ..Default::default()
+        };
+
+        // the grid starts 16 pixels right of Tiled's origin
+        let features = MapFeatures::from_layers(&[layer.clone()], Vector::new(16.0, 0.0)).unwrap();
+        assert_eq!(features.player_spawns, vec![Vector::new(84.0, 50.0)]);
+        let car = &features.vehicle_spawns[0];
+        assert_eq!(car.name.as_deref(), Some("cop car"));
+        assert_eq!(car.skin, CarSkin::Police);
+        assert!(!car.parked);
+        assert!((car.heading - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        let trigger = &features.triggers[0];
+        assert_eq!(trigger.mission, "heist");
+        assert!(trigger.area.contains(Vector::new(40.0, 20.0)));
+        assert!(!trigger.area.contains(Vector::new(60.0, 20.0)));
+
+        let ammo = &features.item_spawns[0];
+        assert_eq!(ammo.position, Vector::new(16.0, 64.0));
+        assert_eq!(
+            ammo.stack,
+            ItemStack::new(ItemKind::Ammo(WeaponKind::Rifle), 90)
+        );
+
+        let named = |id| MapObject {
+            name: "cop car".to_string(),
+            ..object(id, "vehicle_spawn", 0.0, 0.0)
+        };
+        layer.objects.push(named(8));
+        let errors = MapFeatures::from_layers(&[layer.clone()], Vector::ZERO).unwrap_err();
+        assert_eq!(
+            errors,
+            vec!["object 8 in layer 'features': duplicate vehicle name 'cop car'"]
+        );
+        layer.objects.pop();
+
+        layer.objects.push(object(5, "ufo", 0.0, 0.0));
+        layer.objects.push(MapObject {
+            properties: vec![
+                property("item", PropertyValue::String("medkit".to_string())),
+                property("quantity", PropertyValue::Int(0)),
+            ],
+            ..object(7, "item_spawn", 0.0, 0.0)
+        });
+        let errors = MapFeatures::from_layers(&[layer], Vector::ZERO).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "object 5 in layer 'features': unknown object type 'ufo'",
+                "object 7 in layer 'features': bad quantity 0"
+            ]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::error::{AppError, AppResult};
+
+use super::map::Map;
+
+pub const DEFAULT_MAP: &str = "suburb";
+// built into the binary so the server can still start without a map directory
+const BUNDLED_DEFAULT_MAP: &str = include_str!("maps/suburb.json");
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone)]
+pub struct MapInfo {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub player_spawns: usize,
+}
+
+// every map in the map directory, parsed once at startup and shared by all
+// the lobbies playing it
+#[derive(Debug, Default)]
+pub struct MapRegistry {
+    maps: BTreeMap<String, Arc<Map>>,
+}
+
+impl MapRegistry {
+    // a map is named after its file, maps that don't parse are left out
+    // unless it's the default one
+    pub fn load(dir: &Path) -> AppResult<Self> {
+        let entries = fs::read_dir(dir).map_err(|e| {
+            AppError::InternalServerError(format!("Unable to read map directory {:?}: {}", dir, e))
+        })?;
+
+        let mut maps = BTreeMap::new();
+        let paths = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"));
+        for path in paths {
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let map = fs::read_to_string(&path)
+                .map_err(|e| e.into())
+                .and_then(|json| Map::from_json(&json));
+            match map {
+                Ok(mut map) => {
+                    map.name = name.to_string();
+                    maps.insert(name.to_string(), Arc::new(map));
+                }
+                Err(e) if name == DEFAULT_MAP => {
+                    return Err(AppError::InternalServerError(format!(
+                        "Unable to load the default map {:?}: {}",
+                        path, e
+                    )))
+                }
+                Err(e) => eprintln!("Unable to load map {:?}: {}", path, e),
+            }
+        }
+
+        Ok(Self { maps })
+    }
+
+    // the server won't start without the default map
+    pub fn from_env() -> AppResult<Self> {
+        let registry = match map_dir() {
+            Some(dir) => Self::load(&dir)?,
+            None => {
+                eprintln!(
+                    "MAP_DIR is not set, only the bundled {} map is available",
+                    DEFAULT_MAP
+                );
+                Self::bundled()
+            }
+        };
+        if registry.default_map().is_err() {
+            return Err(AppError::InternalServerError(format!(
+                "The default map {} is missing",
+                DEFAULT_MAP
+            )));
+        }
+
+        Ok(registry)
+    }
+
+    // just the default map, as built into the binary
+    pub fn bundled() -> Self {
+        let mut map =
+            Map::from_json(BUNDLED_DEFAULT_MAP).expect("Unable to parse the bundled default map");
+        map.name = DEFAULT_MAP.to_string();
+
+        Self {
+            maps: BTreeMap::from([(DEFAULT_MAP.to_string(), Arc::new(map))]),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> AppResult<Arc<Map>> {
+        self.maps
+            .get(name)
+            .cloned()
+            .ok_or(AppError::BadRequest(format!("No such map: {}", name)))
+    }
+
+    pub fn default_map(&self) -> AppResult<Arc<Map>> {
+        self.get(DEFAULT_MAP)
+    }
+
+    pub fn list(&self) -> Vec<MapInfo> {
+        self.maps
+            .iter()
+            .map(|(name, map)| MapInfo {
+                name: name.clone(),
+                width: map.width(),
+                height: map.height(),
+                player_spawns: map.features().player_spawns.len(),
+            })
+            .collect()
+    }
+}
+
+pub fn map_dir() -> Option<PathBuf> {
+    dotenv::var("MAP_DIR").ok().map(PathBuf::from)
+}
+
+mod test {
+    use std::sync::Arc;
+
+    use super::{MapRegistry, BUNDLED_DEFAULT_MAP, DEFAULT_MAP};
+
+    #[test]
+    fn test() {
+        let dir = std::env::temp_dir().join(format!("maps-{}", ulid::Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("suburb.json"), BUNDLED_DEFAULT_MAP).unwrap();
+        std::fs::write(dir.join("copy.json"), BUNDLED_DEFAULT_MAP).unwrap();
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a map").unwrap();
+
+        let registry = MapRegistry::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let names: Vec<String> = registry.list().into_iter().map(|map| map.name).collect();
+        assert_eq!(names, vec!["copy", "suburb"]);
+        let map = registry.default_map().unwrap();
+        assert_eq!(map.name, DEFAULT_MAP);
+        // every lobby gets the same parsed map
+        assert!(Arc::ptr_eq(&map, &registry.get(DEFAULT_MAP).unwrap()));
+        assert!(registry.get("broken").is_err());
+
+        // a broken default map stops the server rather than being skipped
+        let dir = std::env::temp_dir().join(format!("maps-{}", ulid::Ulid::new()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("suburb.json"), "{").unwrap();
+        assert!(MapRegistry::load(&dir).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+
+        let bundled = MapRegistry::bundled().default_map().unwrap();
+        assert_eq!(bundled.name, DEFAULT_MAP);
+    }
+}
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::f32::consts::FRAC_PI_2;
 
 use std::fmt::Debug;
 use std::ops::Deref;
@@ -8,29 +9,89 @@ use std::time::Duration;
 use std::{future::Future, pin::Pin};
 
 use action::{Action, ActionBuilder, ActionTrigger, ActionTriggerType};
-use axum::async_trait;
-use map::{pixel_to_tile, Coordinates, Map};
+use collision::{Body, CollisionEvent, Shape};
+use events::{DamageSource, EventLog, GameEventKind};
+use history::{EntitySnapshot, HistoryFrame, PositionHistory};
+use input_log::{GameCommand, InputLog, InputRecord, TickInputs};
+use inventory::{Inventory, InventoryAction, ItemKind, ItemStack};
+use item::WorldItem;
+use map::{pixel_to_tile, Map};
+use map_features::MapFeatures;
+use path_planner::PathPlanner;
+use pedestrian::{Pedestrian, PedestrianManager};
+use police::{Crime, PoliceUnit, Wanted};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use replay::{ReplayHeader, ReplayRecorder, REPLAY_VERSION};
 use serde::{Deserialize, Serialize};
 use specta::Type;
+use theft::PullOut;
 use tokio::sync::Mutex;
 use tokio::time::interval;
-use vehicle::Vehicle;
+use traffic::TrafficManager;
+use traffic_light::TrafficLight;
+use vector::{wrap_angle, Vector};
+use vehicle::{DamageState, DriveControls, Seat, Vehicle, VehicleOwner};
+use weapon::{WeaponKind, WeaponState};
 
 use crate::error::{AppError, AppResult};
 
 pub mod action;
+pub mod avoidance;
+pub mod collision;
+pub mod events;
+pub mod history;
+pub mod input_log;
+pub mod inventory;
+pub mod item;
 pub mod map;
+pub mod map_features;
+pub mod map_registry;
+pub mod path_planner;
+pub mod pathfinding;
+pub mod pedestrian;
+pub mod police;
+pub mod replay;
+pub mod road_graph;
+pub mod theft;
+pub mod traffic;
 pub mod traffic_light;
+pub mod vector;
 pub mod vehicle;
+pub mod weapon;
+
+pub(crate) const INTERACTION_RANGE: f32 = 48.0;
+const EXIT_DISTANCE: f32 = 24.0;
+const PLAYER_RADIUS: f32 = 6.0;
+const PLAYER_MASS: f32 = 80.0;
+pub const MAX_HEALTH: f32 = 100.0;
+const RESPAWN_DELAY: Duration = Duration::from_secs(5);
+// damage per px/s a car is doing above the minimum when it hits someone
+const VEHICLE_HIT_MIN_SPEED: f32 = 30.0;
+const VEHICLE_HIT_DAMAGE: f32 = 0.6;
+// same for bailing out of a moving car
+const FALL_MIN_SPEED: f32 = 60.0;
+const FALL_DAMAGE: f32 = 0.4;
+// car damage per px/s of impact above the minimum
+const CRASH_MIN_SPEED: f32 = 40.0;
+const CRASH_DAMAGE: f32 = 0.25;
+const EXPLOSION_RADIUS: f32 = 64.0;
+// at the centre, falling off linearly to nothing at the edge
+const EXPLOSION_DAMAGE: f32 = 80.0;
+const WRECK_DESPAWN: Duration = Duration::from_secs(30);
+const MEDKIT_HEAL: f32 = 50.0;
+const ITEM_DESPAWN: Duration = Duration::from_secs(60);
+// dropped items are spread in a ring this far from where they were dropped
+const DROP_SPREAD: f32 = 12.0;
 
 #[derive(Type, Serialize, Deserialize, Debug, Clone)]
 pub struct OutgoingGameObject {
     pub id: String,
-    pub x: i32,
-    pub y: i32,
+    pub x: f32,
+    pub y: f32,
     pub rotation: f32,
-    pub velocity: Coordinates,
-    pub owner_user_id: String,
+    pub velocity: Vector,
+    pub angular_velocity: f32,
+    pub owner_user_id: Option<String>,
     pub controller_user_id: Option<String>,
     pub details: GameObjectInfo,
     pub action: Option<ActionTrigger>,
@@ -40,6 +101,17 @@ pub struct OutgoingGameObject {
 pub struct PersonDetails {
     pub user_id: String,
     pub skin: PersonSkin,
+    pub vehicle_id: Option<String>,
+    pub health: f32,
+    pub alive: bool,
+    pub weapon: WeaponKind,
+    pub wanted_level: u8,
+    // the car they're dragging the driver out of
+    pub pulling_out_of: Option<String>,
+    // a pedestrian rather than a connected player
+    pub npc: bool,
+    // the named map zone they're in
+    pub zone: Option<String>,
 }
 
 impl PersonDetails {
@@ -47,6 +119,14 @@ impl PersonDetails {
         Self {
             user_id,
             skin: PersonSkin::Default,
+            vehicle_id: None,
+            health: MAX_HEALTH,
+            alive: true,
+            weapon: WeaponState::default().kind,
+            wanted_level: 0,
+            pulling_out_of: None,
+            npc: false,
+            zone: None,
         }
     }
 }
@@ -54,46 +134,71 @@ impl PersonDetails {
 #[derive(Type, Deserialize, Serialize, Debug, Clone)]
 pub struct CarDetails {
     pub skin: CarSkin,
-    pub speed: u16,
-    pub acceleration: u16,
+    pub speed: f32,
+    pub current_speed: f32,
+    pub acceleration: f32,
     pub max_passengers: u8,
     pub passenger_user_ids: Vec<String>,
-    pub rotation_speed: u16,
+    pub rotation_speed: f32,
     pub driver_user_id: Option<String>,
+    pub health: f32,
+    pub max_health: f32,
+    pub damage: DamageState,
 }
 
 impl CarDetails {
     pub fn new(
         skin: CarSkin,
-        speed: u16,
-        acceleration: u16,
-        rotation_speed: u16,
+        speed: f32,
+        acceleration: f32,
+        rotation_speed: f32,
         max_passengers: u8,
+        max_health: f32,
     ) -> Self {
         Self {
             skin,
             speed,
+            current_speed: 0.0,
             acceleration,
             max_passengers,
             passenger_user_ids: vec![],
             rotation_speed,
             driver_user_id: None,
+            health: max_health,
+            max_health,
+            damage: DamageState::Intact,
         }
     }
 }
 
+#[derive(Type, Deserialize, Serialize, Debug, Clone)]
+pub struct ItemDetails {
+    pub kind: ItemKind,
+    pub quantity: u32,
+}
+
 #[derive(Type, Deserialize, Serialize, Debug, Clone)]
 pub enum GameObjectInfo {
     Person(PersonDetails),
     Car(CarDetails),
+    Item(ItemDetails),
 }
 
-#[derive(Type, Deserialize, Serialize, Debug, Clone)]
+#[derive(Type, Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub enum CarSkin {
     Sedan,
     Police,
 }
 
+impl CarSkin {
+    pub fn max_passengers(&self) -> u8 {
+        match self {
+            CarSkin::Sedan => 3,
+            CarSkin::Police => 1,
+        }
+    }
+}
+
 #[derive(Type, Deserialize, Serialize, Debug, Clone)]
 pub enum PersonSkin {
     Default,
@@ -106,42 +211,147 @@ pub struct Player {
     pub x: i32,
     pub y: i32,
     pub rotation: f32,
-    pub velocity: Coordinates,
+    pub velocity: Vector,
+    pub angular_velocity: f32,
+    pub vehicle_id: Option<String>,
+    pub health: f32,
+    pub weapon: WeaponState,
+    pub inventory: Inventory,
+    #[serde(skip)]
+    pub wanted: Wanted,
+    #[serde(skip)]
+    pub pull_out: Option<PullOut>,
+    // indexes of the map triggers they're standing in
+    #[serde(skip)]
+    triggers: BTreeSet<usize>,
+    #[serde(skip)]
+    zone: Option<String>,
+    #[serde(skip)]
+    respawn_at: Option<Duration>,
+    #[serde(skip)]
+    previous_position: Vector,
+    #[serde(skip)]
+    previous_rotation: f32,
 }
 
 impl Player {
     pub fn to_outgoing_game_object(&self) -> OutgoingGameObject {
         OutgoingGameObject {
             id: self.id.clone(),
-            x: self.x.clone(),
-            y: self.y.clone(),
+            x: self.x as f32,
+            y: self.y as f32,
             rotation: self.rotation.clone(),
-            velocity: self.velocity.clone(),
-            owner_user_id: self.id.clone(),
+            velocity: self.velocity,
+            angular_velocity: self.angular_velocity,
+            owner_user_id: Some(self.id.clone()),
             controller_user_id: Some(self.id.clone()),
             action: None,
             details: GameObjectInfo::Person(PersonDetails {
                 user_id: self.id.clone(),
                 skin: self.skin.clone(),
+                vehicle_id: self.vehicle_id.clone(),
+                health: self.health,
+                alive: self.is_alive(),
+                weapon: self.weapon.kind,
+                wanted_level: self.wanted.level(),
+                pulling_out_of: self
+                    .pull_out
+                    .as_ref()
+                    .map(|pull_out| pull_out.vehicle_id.clone()),
+                npc: false,
+                zone: self.zone.clone(),
             }),
         }
     }
 
-    fn new(id: String) -> Self {
-        Self {
+    fn new(id: String, position: Vector) -> Self {
+        let mut player = Self {
             id,
             skin: PersonSkin::Default,
-            x: 608,
-            y: 800,
+            x: 0,
+            y: 0,
             rotation: 0.0,
-            velocity: Coordinates { x: 0, y: 0 },
-        }
+            velocity: Vector::ZERO,
+            angular_velocity: 0.0,
+            vehicle_id: None,
+            health: MAX_HEALTH,
+            weapon: WeaponState::default(),
+            inventory: Inventory::starting(),
+            wanted: Wanted::default(),
+            pull_out: None,
+            triggers: BTreeSet::new(),
+            zone: None,
+            respawn_at: None,
+            previous_position: position,
+            previous_rotation: 0.0,
+        };
+        player.set_position(position);
+        player
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.health > 0.0
+    }
+
+    fn respawn(&mut self, position: Vector) {
+        self.health = MAX_HEALTH;
+        self.weapon = WeaponState::default();
+        self.inventory = Inventory::starting();
+        self.respawn_at = None;
+        self.set_position(position);
+        self.previous_position = position;
+        self.velocity = Vector::ZERO;
+    }
+
+    pub fn position(&self) -> Vector {
+        Vector::new(self.x as f32, self.y as f32)
+    }
+
+    fn set_position(&mut self, position: Vector) {
+        self.x = position.x.round() as i32;
+        self.y = position.y.round() as i32;
     }
 
     fn input(&mut self, input: PlayerInput) {
         self.x = input.x;
         self.y = input.y;
-        self.rotation = input.rotation;
+        // the rotation is whatever the client sent, junk keeps the old one
+        if input.rotation.is_finite() {
+            self.rotation = wrap_angle(input.rotation);
+        }
+    }
+
+    // positions come from the client, so the velocity going into collision is
+    // how far they moved since the last tick
+    fn body(&self, dt: Duration) -> Body {
+        let dt = dt.as_secs_f32();
+        let velocity = if dt > 0.0 {
+            (self.position() - self.previous_position) * (1.0 / dt)
+        } else {
+            Vector::ZERO
+        };
+
+        Body {
+            id: self.id.clone(),
+            position: self.position(),
+            heading: self.rotation,
+            velocity,
+            shape: Shape::Circle {
+                radius: PLAYER_RADIUS,
+            },
+            mass: PLAYER_MASS,
+        }
+    }
+
+    fn update_motion(&mut self, dt: Duration) {
+        let position = self.position();
+        let dt = dt.as_secs_f32();
+        if dt > 0.0 {
+            self.velocity = (position - self.previous_position) * (1.0 / dt);
+            self.angular_velocity = wrap_angle(self.rotation - self.previous_rotation) / dt;
+        }
+        self.previous_position = position;
+        self.previous_rotation = self.rotation;
     }
 }
 
@@ -153,33 +363,96 @@ pub struct GameObject {
     pub details: GameObjectType,
 }
 impl GameObject {
-    async fn tick(&mut self) -> AppResult<()> {
+    fn tick(&mut self, dt: Duration) -> AppResult<()> {
         match &mut self.details {
-            GameObjectType::Car(car) => car.tick(),
+            GameObjectType::Car(car) => car.tick(dt),
+            GameObjectType::Pedestrian(pedestrian) => pedestrian.tick(dt),
+            GameObjectType::Item(_) => {}
         }
         Ok(())
     }
 
-    async fn apply_action(
-        &mut self,
-        get_state: Arc<Mutex<GameState>>,
-        user_id: String,
-    ) -> AppResult<()> {
+    fn snapshot(&self) -> EntitySnapshot {
+        match &self.details {
+            GameObjectType::Car(car) => EntitySnapshot {
+                position: car.position,
+                rotation: car.rotation(),
+            },
+            GameObjectType::Pedestrian(pedestrian) => EntitySnapshot {
+                position: pedestrian.position,
+                rotation: pedestrian.rotation(),
+            },
+            GameObjectType::Item(item) => EntitySnapshot {
+                position: item.position,
+                rotation: 0.0,
+            },
+        }
+    }
+
+    // items on the ground don't collide or stop bullets
+    fn body(&self, id: &str) -> Option<Body> {
+        match &self.details {
+            GameObjectType::Car(car) => Some(Body {
+                id: id.to_string(),
+                position: car.position,
+                heading: car.heading,
+                velocity: car.velocity,
+                shape: Shape::Box {
+                    half_length: car.params.length / 2.0,
+                    half_width: car.params.width / 2.0,
+                },
+                mass: car.params.mass,
+            }),
+            GameObjectType::Pedestrian(pedestrian) => pedestrian.body(id),
+            GameObjectType::Item(_) => None,
+        }
+    }
+
+    fn apply_body(&mut self, body: &Body) {
         match &mut self.details {
-            GameObjectType::Car(car) => car.action(user_id),
+            GameObjectType::Car(car) => {
+                car.position = body.position;
+                // the car can only roll along its heading, whatever sideways
+                // push it took is absorbed by the tyres
+                car.speed = body.velocity.dot(car.heading());
+                car.velocity = car.heading() * car.speed;
+            }
+            GameObjectType::Pedestrian(pedestrian) => pedestrian.position = body.position,
+            GameObjectType::Item(_) => {}
         }
-        Ok(())
     }
 }
 
 pub enum GameObjectType {
     Car(Vehicle),
+    Pedestrian(Pedestrian),
+    Item(WorldItem),
 }
 
+// players and objects live in BTreeMaps so every tick visits them in the same
+// order, which together with the seeded rng keeps the simulation replayable
 pub struct GameState {
-    pub players: HashMap<String, Player>,
-    pub objects: HashMap<String, GameObject>,
-    pub map: Map,
+    pub players: BTreeMap<String, Player>,
+    pub objects: BTreeMap<String, GameObject>,
+    // shared with every other game on the same map
+    pub map: Arc<Map>,
+    pub tick: u32,
+    pub time: Duration,
+    pub history: PositionHistory,
+    pub rng: SmallRng,
+    pub input_log: InputLog,
+    // collisions resolved during the last step
+    pub collisions: Vec<CollisionEvent>,
+    pub events: EventLog,
+    // police cars chasing someone, by vehicle id
+    pub police: BTreeMap<String, PoliceUnit>,
+    pub traffic: TrafficManager,
+    pub traffic_lights: Vec<TrafficLight>,
+    pub paths: PathPlanner,
+    pub pedestrians: PedestrianManager,
+    next_object_id: u32,
+    pending_inputs: Vec<InputRecord>,
+    recorder: Option<ReplayRecorder>,
 }
 
 impl Debug for GameState {
@@ -191,42 +464,1053 @@ impl Debug for GameState {
 }
 
 impl GameState {
-    pub fn default() -> Self {
-        let mut players = HashMap::new();
-        let mut objects = HashMap::new();
-        players.insert("tim".to_string(), Player::new("tim".to_string()));
-        players.insert("bob".to_string(), Player::new("bob".to_string()));
-        let map = Map::from_json(include_str!("maps/suburb.json")).unwrap();
-
-        let mut vehicle = Vehicle::new(
-            "tim's car".to_string(),
-            Coordinates { x: 160, y: 368 },
-            vehicle::VehicleBehavior::Normal,
-            2,
-        );
+    pub fn new(seed: u64, map: Arc<Map>) -> Self {
+        let spawns = &map.features().player_spawns;
+        let mut players = BTreeMap::new();
+        for (index, id) in ["tim", "bob"].into_iter().enumerate() {
+            let position = match spawns.is_empty() {
+                true => map.center(),
+                false => spawns[index % spawns.len()],
+            };
+            players.insert(id.to_string(), Player::new(id.to_string(), position));
+        }
 
-        let path = map
-            .find_path(
-                pixel_to_tile(vehicle.position),
-                pixel_to_tile(Coordinates { x: 1360, y: 1360 }),
-            )
-            .expect("no path found");
+        let mut state = Self {
+            players,
+            objects: BTreeMap::new(),
+            map,
+            tick: 0,
+            time: Duration::ZERO,
+            history: PositionHistory::default(),
+            rng: SmallRng::seed_from_u64(seed),
+            input_log: InputLog::new(seed),
+            collisions: vec![],
+            events: EventLog::default(),
+            police: BTreeMap::new(),
+            traffic: TrafficManager::default(),
+            traffic_lights: vec![],
+            paths: PathPlanner::default(),
+            pedestrians: PedestrianManager::default(),
+            next_object_id: 0,
+            pending_inputs: vec![],
+            recorder: None,
+        };
+
+        state.spawn_map_vehicles();
+
+        // every junction gets lights, each starting somewhere in its cycle
+        for (index, intersection) in state.map.intersections().iter().enumerate() {
+            let offset = state.rng.gen_range(Duration::ZERO..TrafficLight::cycle());
+            let id = format!("light-{}", index + 1);
+            state
+                .traffic_lights
+                .push(TrafficLight::new(id, intersection, offset));
+        }
+
+        state.spawn_map_items();
+
+        state
+    }
+
+    // re-runs a match from its seed, feeding each logged command back in on
+    // the tick it was originally applied
+    pub fn replay(log: &InputLog, map: Arc<Map>) -> Self {
+        let mut state = Self::new(log.seed, map);
+        while state.step_from_log(log) {}
+        state
+    }
+
+    // advances one tick using the inputs logged for it, returning false once
+    // the log has been played to the end
+    pub fn step_from_log(&mut self, log: &InputLog) -> bool {
+        if self.tick >= log.total_ticks {
+            return false;
+        }
+
+        self.pending_inputs = log.inputs_for(self.tick + 1).to_vec();
+        self.step(log.timestep);
+        true
+    }
+
+    pub fn start_recording(&mut self, path: &std::path::Path, timestep: Duration) -> AppResult<()> {
+        let header = ReplayHeader {
+            version: REPLAY_VERSION,
+            map: self.map.name.clone(),
+            seed: self.seed(),
+            timestep,
+            players: self.players.keys().cloned().collect(),
+        };
+        let recorder = ReplayRecorder::create(path, &header)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+        self.recorder = Some(recorder);
+
+        Ok(())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.input_log.seed
+    }
+
+    pub fn queue_input(&mut self, user_id: String, command: GameCommand) {
+        self.pending_inputs.push(InputRecord { user_id, command });
+    }
 
-        vehicle.set_tile_path(path);
+    fn apply_command(&mut self, record: &InputRecord) {
+        if !self
+            .players
+            .get(&record.user_id)
+            .is_some_and(|player| player.is_alive())
+        {
+            return;
+        }
 
-        objects.insert(
-            vehicle.id.clone(),
+        match &record.command {
+            GameCommand::Move { x, y, rotation } => {
+                if let Some(player) = self.players.get_mut(&record.user_id) {
+                    // while seated the player just rides along with the car
+                    if player.vehicle_id.is_none() {
+                        player.input(PlayerInput {
+                            x: *x,
+                            y: *y,
+                            rotation: *rotation,
+                            controls: None,
+                        })
+                    }
+                }
+            }
+            GameCommand::Drive(controls) => self.drive(&record.user_id, *controls),
+            GameCommand::SwitchSeat { seat } => {
+                if let Err(e) = self.switch_seat(&record.user_id, *seat) {
+                    eprintln!("{} could not switch seats: {:?}", record.user_id, e);
+                }
+            }
+            GameCommand::Action { object_id } => {
+                if let Err(e) = self.use_object(&record.user_id, object_id) {
+                    eprintln!("action on {} failed: {:?}", object_id, e);
+                }
+            }
+            GameCommand::Inventory(action) => {
+                if let Err(e) = self.inventory_action(&record.user_id, *action) {
+                    eprintln!("{} inventory action failed: {:?}", record.user_id, e);
+                }
+            }
+            GameCommand::Fire { aim, client_tick } => {
+                if let Err(e) = self.fire(&record.user_id, *aim, *client_tick) {
+                    eprintln!("{} could not fire: {:?}", record.user_id, e);
+                }
+            }
+        }
+    }
+
+    pub fn spawn_item(&mut self, stack: ItemStack, position: Vector, despawn: bool) -> String {
+        self.next_object_id += 1;
+        let id = format!("item-{}", self.next_object_id);
+        let item = WorldItem {
+            id: id.clone(),
+            position,
+            stack,
+            despawn_at: despawn.then(|| self.time + ITEM_DESPAWN),
+        };
+        self.objects.insert(
+            id.clone(),
             GameObject {
-                details: GameObjectType::Car(vehicle),
+                details: GameObjectType::Item(item),
             },
         );
 
-        Self {
-            players,
-            objects,
-            map,
+        id
+    }
+
+    // takes as much of the stack as fits, the rest stays on the ground
+    pub fn pick_up(&mut self, user_id: &str, item_id: &str) -> AppResult<()> {
+        let stack = match self.objects.get(item_id).map(|obj| &obj.details) {
+            Some(GameObjectType::Item(item)) => item.stack,
+            _ => return Err(AppError::BadRequest("No such item".to_string())),
+        };
+        let player = self
+            .players
+            .get_mut(user_id)
+            .ok_or(AppError::BadRequest("Not in this game".to_string()))?;
+
+        match player.inventory.add(stack) {
+            None => {
+                self.objects.remove(item_id);
+            }
+            Some(left) if left == stack => {
+                return Err(AppError::BadRequest("Inventory is full".to_string()));
+            }
+            Some(left) => {
+                if let Some(GameObjectType::Item(item)) =
+                    self.objects.get_mut(item_id).map(|obj| &mut obj.details)
+                {
+                    item.stack = left;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn inventory_action(&mut self, user_id: &str, action: InventoryAction) -> AppResult<()> {
+        let player = self
+            .players
+            .get_mut(user_id)
+            .ok_or(AppError::BadRequest("Not in this game".to_string()))?;
+
+        match action {
+            InventoryAction::Equip { weapon } => {
+                if !player.inventory.has_weapon(weapon) {
+                    return Err(AppError::BadRequest(
+                        "You don't have that weapon".to_string(),
+                    ));
+                }
+                player.weapon.switch(weapon);
+            }
+            InventoryAction::Use { slot } => {
+                let stack = player.inventory.slots.get(slot as usize).copied();
+                match stack.map(|stack| stack.kind) {
+                    Some(ItemKind::Medkit) if player.health < MAX_HEALTH => {
+                        player.inventory.take(ItemKind::Medkit, 1);
+                        player.health = (player.health + MEDKIT_HEAL).min(MAX_HEALTH);
+                    }
+                    Some(ItemKind::Medkit) => {
+                        return Err(AppError::BadRequest("Already at full health".to_string()));
+                    }
+                    Some(_) => return Err(AppError::BadRequest("Can't use that".to_string())),
+                    None => return Err(AppError::BadRequest("Empty slot".to_string())),
+                }
+            }
+            InventoryAction::Drop { slot } => {
+                let stack = player
+                    .inventory
+                    .remove_slot(slot as usize)
+                    .ok_or(AppError::BadRequest("Empty slot".to_string()))?;
+                if !player.inventory.has_weapon(player.weapon.kind) {
+                    player.weapon.switch(WeaponKind::Melee);
+                }
+                let position = player.position();
+                self.spawn_item(stack, position, true);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn drop_inventory(&mut self, user_id: &str) {
+        let Some(player) = self.players.get_mut(user_id) else {
+            return;
+        };
+        let position = player.position();
+        let items = player.inventory.drain();
+        player.weapon.switch(WeaponKind::Melee);
+
+        let count = items.len();
+        for (index, stack) in items.into_iter().enumerate() {
+            let angle = std::f32::consts::TAU * index as f32 / count as f32;
+            self.spawn_item(
+                stack,
+                position + Vector::from_angle(angle) * DROP_SPREAD,
+                true,
+            );
+        }
+    }
+
+    fn despawn_items(&mut self) {
+        let time = self.time;
+        self.objects.retain(|_, obj| match &obj.details {
+            GameObjectType::Item(item) => item.despawn_at.is_none_or(|at| time < at),
+            _ => true,
+        });
+    }
+
+    pub fn fire(&mut self, user_id: &str, aim: Vector, client_tick: Option<u32>) -> AppResult<()> {
+        // a zero, tiny or overflowing length can't be normalized into a direction
+        if !aim.x.is_finite() || !aim.y.is_finite() || !aim.length().is_normal() {
+            return Err(AppError::BadRequest("No aim direction".to_string()));
+        }
+        let direction = aim.normalized();
+
+        let time = self.time;
+        let player = self
+            .players
+            .get_mut(user_id)
+            .ok_or(AppError::BadRequest("Not in this game".to_string()))?;
+        if player.vehicle_id.is_some() {
+            return Err(AppError::BadRequest(
+                "Can't fire from a vehicle".to_string(),
+            ));
+        }
+        if player.pull_out.is_some() {
+            return Err(AppError::BadRequest("Hands are busy".to_string()));
+        }
+        let weapon = player.weapon.kind;
+        let params = player.weapon.try_fire(time, &mut player.inventory)?;
+        let origin = player.position();
+        player.rotation = direction.y.atan2(direction.x) + FRAC_PI_2;
+
+        self.events.push(
+            self.tick,
+            self.time,
+            GameEventKind::Muzzle {
+                shooter: user_id.to_string(),
+                weapon,
+                x: origin.x,
+                y: origin.y,
+                aim: direction,
+            },
+        );
+
+        self.raise_alarm(origin);
+
+        let wall = collision::ray_blocked(&self.map, origin, direction, params.range);
+        let hit = self
+            .hitscan(user_id, origin, direction, params.range, client_tick)
+            .filter(|(_, distance)| wall.is_none_or(|wall| *distance < wall));
+
+        let (target, distance) = match (hit, wall) {
+            (Some((target, distance)), _) => (Some(target), distance),
+            (None, Some(wall)) => (None, wall),
+            (None, None) => return Ok(()),
+        };
+        let point = origin + direction * distance;
+        self.events.push(
+            self.tick,
+            self.time,
+            GameEventKind::Hit {
+                shooter: user_id.to_string(),
+                target: target.clone(),
+                x: point.x,
+                y: point.y,
+            },
+        );
+
+        if let Some(target) = target {
+            let attacker = Some(user_id.to_string());
+            if self.players.contains_key(&target) {
+                let source = DamageSource::Weapon { weapon };
+                self.damage_player(&target, params.damage, source, attacker);
+            } else if self.pedestrian(&target).is_some() {
+                let source = DamageSource::Weapon { weapon };
+                self.damage_pedestrian(&target, params.damage, source, attacker);
+            } else if let Some(car) = self.vehicle_mut(&target) {
+                car.take_damage(params.damage, attacker);
+                if self.is_police_unit(&target) {
+                    self.commit_crime(user_id, Crime::AttackedPolice);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // the closest player or vehicle along the shot, with everyone placed where
+    // the shooter saw them at `client_tick`
+    fn hitscan(
+        &self,
+        shooter: &str,
+        origin: Vector,
+        direction: Vector,
+        range: f32,
+        client_tick: Option<u32>,
+    ) -> Option<(String, f32)> {
+        let mut bodies = vec![];
+        for player in self.players.values() {
+            if player.id == shooter || player.vehicle_id.is_some() || !player.is_alive() {
+                continue;
+            }
+            if let Some(snapshot) = self.rewound_entity(&player.id, client_tick) {
+                bodies.push(Body {
+                    position: snapshot.position,
+                    heading: snapshot.rotation,
+                    shape: Shape::Circle {
+                        radius: PLAYER_RADIUS,
+                    },
+                    ..player.body(Duration::ZERO)
+                });
+            }
+        }
+        for (id, obj) in self.objects.iter() {
+            let (Some(body), Some(snapshot)) = (obj.body(id), self.rewound_entity(id, client_tick))
+            else {
+                continue;
+            };
+            bodies.push(Body {
+                position: snapshot.position,
+                // snapshots store the sprite rotation, a quarter turn ahead
+                heading: snapshot.rotation - FRAC_PI_2,
+                ..body
+            });
+        }
+
+        bodies
+            .iter()
+            .filter_map(|body| {
+                collision::ray_cast(origin, direction, range, body).map(|distance| (body, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(body, distance)| (body.id.clone(), distance))
+    }
+
+    fn use_object(&mut self, user_id: &str, object_id: &str) -> AppResult<()> {
+        let obj = self
+            .objects
+            .get(object_id)
+            .ok_or(AppError::BadRequest("No such object".to_string()))?;
+
+        let pulling_out = self
+            .players
+            .get(user_id)
+            .and_then(|player| player.pull_out.as_ref())
+            .is_some_and(|pull_out| pull_out.vehicle_id == object_id);
+
+        match &obj.details {
+            // using the car again lets go of the driver
+            GameObjectType::Car(_) if pulling_out => {
+                self.interrupt_pull_out(user_id);
+                Ok(())
+            }
+            GameObjectType::Car(car) if car.is_occupant(user_id) => self.exit_vehicle(user_id),
+            GameObjectType::Car(_) => self.enter_vehicle(user_id, object_id),
+            GameObjectType::Item(_) => self.pick_up(user_id, object_id),
+            GameObjectType::Pedestrian(_) => {
+                Err(AppError::BadRequest("Nothing to use".to_string()))
+            }
+        }
+    }
+
+    fn vehicle(&self, vehicle_id: &str) -> Option<&Vehicle> {
+        match &self.objects.get(vehicle_id)?.details {
+            GameObjectType::Car(car) => Some(car),
+            _ => None,
+        }
+    }
+
+    fn vehicle_mut(&mut self, vehicle_id: &str) -> Option<&mut Vehicle> {
+        match &mut self.objects.get_mut(vehicle_id)?.details {
+            GameObjectType::Car(car) => Some(car),
+            _ => None,
+        }
+    }
+
+    pub fn enter_vehicle(&mut self, user_id: &str, vehicle_id: &str) -> AppResult<()> {
+        let player = self
+            .players
+            .get(user_id)
+            .ok_or(AppError::BadRequest("Not in this game".to_string()))?;
+        if player.vehicle_id.is_some() {
+            return Err(AppError::BadRequest("Already in a vehicle".to_string()));
+        }
+        if player.pull_out.is_some() {
+            return Err(AppError::BadRequest(
+                "Already stealing a vehicle".to_string(),
+            ));
+        }
+
+        let car = self
+            .vehicle_mut(vehicle_id)
+            .ok_or(AppError::BadRequest("No such vehicle".to_string()))?;
+        if car.is_wrecked() {
+            return Err(AppError::BadRequest("Vehicle is wrecked".to_string()));
+        }
+        let stolen = car.driver_user_id.is_none() && !car.has_keys(user_id);
+        if car.driver_user_id.is_none() && car.npc_driver {
+            if stolen {
+                return self.start_pull_out(user_id, vehicle_id);
+            }
+            // the owner's own driver just hands over the keys
+            car.npc_driver = false;
+        }
+        if car.driver_user_id.is_none() {
+            car.take_control(user_id);
+        } else if car.has_free_passenger_seat() {
+            car.passenger_user_ids.push(user_id.to_string());
+        } else {
+            return Err(AppError::BadRequest("Vehicle is full".to_string()));
+        }
+        let (position, rotation) = (car.position, car.rotation());
+
+        if let Some(player) = self.players.get_mut(user_id) {
+            player.vehicle_id = Some(vehicle_id.to_string());
+            player.set_position(position);
+            player.rotation = rotation;
+        }
+        if stolen {
+            self.vehicle_stolen(user_id, vehicle_id);
+        }
+
+        Ok(())
+    }
+
+    // jumping out of a moving car hurts
+    pub fn exit_vehicle(&mut self, user_id: &str) -> AppResult<()> {
+        let speed = self.leave_vehicle(user_id)?;
+        let amount = (speed - FALL_MIN_SPEED).max(0.0) * FALL_DAMAGE;
+        self.damage_player(user_id, amount, DamageSource::Fall, None);
+
+        Ok(())
+    }
+
+    // takes the player out of their seat and returns how fast the car was going
+    fn leave_vehicle(&mut self, user_id: &str) -> AppResult<f32> {
+        let vehicle_id = self
+            .players
+            .get(user_id)
+            .and_then(|player| player.vehicle_id.clone())
+            .ok_or(AppError::BadRequest("Not in a vehicle".to_string()))?;
+
+        let exit = match self.vehicle(&vehicle_id) {
+            Some(car) => self.exit_position(car, car.seat_index(user_id).unwrap_or(0)),
+            None => self
+                .players
+                .get(user_id)
+                .map(|player| player.position())
+                .unwrap_or_default(),
+        };
+        let mut speed = 0.0;
+        if let Some(car) = self.vehicle_mut(&vehicle_id) {
+            speed = car.speed.abs();
+            car.remove_occupant(user_id);
+        }
+
+        if let Some(player) = self.players.get_mut(user_id) {
+            player.vehicle_id = None;
+            player.set_position(exit);
+        }
+
+        Ok(speed)
+    }
+
+    // a walkable spot next to the car; each seat tries the sides in a different
+    // order so a full car doesn't stack everyone on the same spot
+    fn exit_position(&self, car: &Vehicle, seat_index: usize) -> Vector {
+        let position = car.position;
+        let heading = car.heading();
+        let sides = [
+            Vector::new(heading.y, -heading.x),
+            Vector::new(-heading.y, heading.x),
+            heading,
+            heading * -1.0,
+        ];
+
+        (0..sides.len())
+            .map(|i| position + sides[(seat_index + i) % sides.len()] * EXIT_DISTANCE)
+            .find(|spot| self.map.is_walkable(pixel_to_tile(spot.to_coordinates())))
+            .unwrap_or(position)
+    }
+
+    pub fn switch_seat(&mut self, user_id: &str, seat: Seat) -> AppResult<()> {
+        let vehicle_id = self
+            .players
+            .get(user_id)
+            .and_then(|player| player.vehicle_id.clone())
+            .ok_or(AppError::BadRequest("Not in a vehicle".to_string()))?;
+        let car = self
+            .vehicle_mut(&vehicle_id)
+            .ok_or(AppError::BadRequest("No such vehicle".to_string()))?;
+
+        let mut stolen = false;
+        match seat {
+            Seat::Driver if car.driver_user_id.as_deref() == Some(user_id) => {}
+            Seat::Driver if car.driver_user_id.is_some() => {
+                return Err(AppError::BadRequest("Driver seat is taken".to_string()));
+            }
+            Seat::Driver => {
+                car.remove_occupant(user_id);
+                car.take_control(user_id);
+                // sliding over from the passenger seat is still theft
+                stolen = !car.has_keys(user_id);
+            }
+            Seat::Passenger if car.passenger_user_ids.iter().any(|id| id == user_id) => {}
+            Seat::Passenger if !car.has_free_passenger_seat() => {
+                return Err(AppError::BadRequest("No free passenger seat".to_string()));
+            }
+            Seat::Passenger => {
+                car.remove_occupant(user_id);
+                car.passenger_user_ids.push(user_id.to_string());
+            }
+        }
+        if stolen {
+            self.vehicle_stolen(user_id, &vehicle_id);
+        }
+
+        Ok(())
+    }
+
+    // throws everyone out of the car and takes it out of the game
+    pub fn destroy_vehicle(&mut self, vehicle_id: &str) -> Option<GameObject> {
+        let occupants: Vec<String> = self
+            .players
+            .values()
+            .filter(|player| player.vehicle_id.as_deref() == Some(vehicle_id))
+            .map(|player| player.id.clone())
+            .collect();
+        for user_id in occupants {
+            self.exit_vehicle(&user_id).ok();
+        }
+
+        self.objects.remove(vehicle_id)
+    }
+
+    fn drive(&mut self, user_id: &str, controls: DriveControls) {
+        let Some(vehicle_id) = self
+            .players
+            .get(user_id)
+            .and_then(|player| player.vehicle_id.clone())
+        else {
+            return;
+        };
+
+        if let Some(car) = self.vehicle_mut(&vehicle_id) {
+            if car.driver_user_id.as_deref() == Some(user_id) {
+                car.control(controls);
+            }
+        }
+    }
+
+    // seated players follow their vehicle around
+    fn attach_occupants(&mut self) {
+        for player in self.players.values_mut() {
+            let Some(vehicle_id) = &player.vehicle_id else {
+                continue;
+            };
+            if let Some(obj) = self.objects.get(vehicle_id) {
+                match &obj.details {
+                    GameObjectType::Car(car) => {
+                        player.set_position(car.position);
+                        player.rotation = car.rotation();
+                    }
+                    GameObjectType::Pedestrian(_) | GameObjectType::Item(_) => {}
+                }
+            }
+        }
+    }
+
+    pub fn step(&mut self, dt: Duration) {
+        self.tick += 1;
+        self.time += dt;
+        self.events.prune(self.time);
+        self.respawn_players();
+
+        let inputs = std::mem::take(&mut self.pending_inputs);
+        for record in inputs.iter() {
+            self.apply_command(record);
+        }
+        // a recorder streams the inputs to disk, they're only kept in memory
+        // when nothing else has them
+        let inputs = match &mut self.recorder {
+            Some(recorder) => {
+                let tick = TickInputs {
+                    tick: self.tick,
+                    inputs,
+                };
+                match recorder.write_tick(&tick) {
+                    Ok(()) => Vec::new(),
+                    Err(e) => {
+                        eprintln!("Stopped recording replay: {:?}", e);
+                        self.recorder = None;
+                        tick.inputs
+                    }
+                }
+            }
+            None => inputs,
+        };
+        self.input_log.record(self.tick, dt, inputs);
+
+        self.update_traffic_lights(dt);
+        self.update_ai_drivers();
+        for obj in self.objects.values_mut() {
+            obj.tick(dt).expect("hmm");
+        }
+        self.resolve_collisions(dt);
+        self.update_wrecks();
+        self.despawn_items();
+        self.update_traffic();
+        self.update_pedestrians();
+        self.update_pull_outs();
+        self.update_police(dt);
+        self.update_paths();
+        self.attach_occupants();
+        self.update_triggers();
+        for player in self.players.values_mut() {
+            player.update_motion(dt);
+        }
+
+        self.record_history();
+    }
+
+    // seated players ride inside their vehicle's box and are left out
+    fn resolve_collisions(&mut self, dt: Duration) {
+        let mut bodies: Vec<Body> = self
+            .players
+            .values()
+            .filter(|player| player.vehicle_id.is_none() && player.is_alive())
+            .map(|player| player.body(dt))
+            .collect();
+        bodies.extend(self.objects.iter().filter_map(|(id, obj)| obj.body(id)));
+        let before: HashMap<String, Body> = bodies
+            .iter()
+            .map(|body| (body.id.clone(), body.clone()))
+            .collect();
+
+        self.collisions = collision::resolve(&mut bodies, &self.map);
+        for event in self.collisions.clone() {
+            self.crash_damage(&event);
+            if let Some(b) = &event.b {
+                self.vehicle_hit(&before, &event.a, b);
+                self.vehicle_hit(&before, b, &event.a);
+            }
+        }
+
+        for body in bodies.iter() {
+            if let Some(player) = self.players.get_mut(&body.id) {
+                player.set_position(body.position);
+            } else if let Some(obj) = self.objects.get_mut(&body.id) {
+                obj.apply_body(body);
+            }
         }
     }
+
+    // cars take damage from hitting walls and each other, running people over
+    // doesn't hurt the car
+    fn crash_damage(&mut self, event: &CollisionEvent) {
+        let amount = (event.impact_speed - CRASH_MIN_SPEED).max(0.0) * CRASH_DAMAGE;
+        let driver = |id: &str| self.vehicle(id).and_then(|car| car.driver_user_id.clone());
+
+        match &event.b {
+            None => {
+                if let Some(car) = self.vehicle_mut(&event.a) {
+                    car.take_damage(amount, None);
+                }
+            }
+            Some(b) if self.vehicle(&event.a).is_some() && self.vehicle(b).is_some() => {
+                let (a_driver, b_driver) = (driver(&event.a), driver(b));
+                if let Some(car) = self.vehicle_mut(&event.a) {
+                    car.take_damage(amount, b_driver);
+                }
+                if let Some(car) = self.vehicle_mut(b) {
+                    car.take_damage(amount, a_driver);
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn update_wrecks(&mut self) {
+        let mut wrecked = vec![];
+        let mut expired = vec![];
+        for (id, obj) in self.objects.iter() {
+            match &obj.details {
+                GameObjectType::Car(car) => match car.wrecked_at {
+                    None if car.health <= 0.0 => wrecked.push(id.clone()),
+                    Some(at) if self.time >= at + WRECK_DESPAWN => expired.push(id.clone()),
+                    _ => {}
+                },
+                GameObjectType::Pedestrian(_) | GameObjectType::Item(_) => {}
+            }
+        }
+
+        for id in wrecked {
+            self.wreck_vehicle(&id);
+        }
+        for id in expired {
+            self.destroy_vehicle(&id);
+        }
+    }
+
+    // blows the car up: everyone inside is thrown out and anyone close by is
+    // caught in the explosion, the wreck stays until WRECK_DESPAWN
+    fn wreck_vehicle(&mut self, vehicle_id: &str) {
+        let time = self.time;
+        let Some(car) = self.vehicle_mut(vehicle_id) else {
+            return;
+        };
+        car.wrecked_at = Some(time);
+        let (position, attacker) = (car.position, car.last_damaged_by.clone());
+
+        let occupants: Vec<String> = self
+            .players
+            .values()
+            .filter(|player| player.vehicle_id.as_deref() == Some(vehicle_id))
+            .map(|player| player.id.clone())
+            .collect();
+        for user_id in occupants {
+            self.exit_vehicle(&user_id).ok();
+        }
+
+        self.events.push(
+            self.tick,
+            self.time,
+            GameEventKind::Explosion {
+                vehicle_id: vehicle_id.to_string(),
+                x: position.x,
+                y: position.y,
+                radius: EXPLOSION_RADIUS,
+            },
+        );
+
+        let players = self
+            .players
+            .values()
+            .map(|player| (player.id.clone(), player.position()));
+        let pedestrians = self
+            .pedestrians
+            .pedestrians()
+            .filter_map(|id| self.pedestrian(id))
+            .map(|pedestrian| (pedestrian.id.clone(), pedestrian.position));
+        let caught: Vec<(String, f32)> = players
+            .chain(pedestrians)
+            .map(|(id, at)| (id, 1.0 - (at - position).length() / EXPLOSION_RADIUS))
+            .filter(|(_, falloff)| *falloff > 0.0)
+            .collect();
+        for (id, falloff) in caught {
+            let source = DamageSource::Explosion {
+                vehicle_id: vehicle_id.to_string(),
+            };
+            self.damage_person(&id, EXPLOSION_DAMAGE * falloff, source, attacker.clone());
+        }
+        self.raise_alarm(position);
+    }
+
+    // a car running into someone hurts them by how fast the car was moving
+    // towards them, so walking into a parked car is harmless
+    fn vehicle_hit(&mut self, before: &HashMap<String, Body>, vehicle_id: &str, user_id: &str) {
+        let (Some(car), Some(person)) = (before.get(vehicle_id), before.get(user_id)) else {
+            return;
+        };
+        if !self.players.contains_key(user_id) && self.pedestrian(user_id).is_none() {
+            return;
+        }
+        let Some(attacker) = self
+            .vehicle(vehicle_id)
+            .map(|car| car.driver_user_id.clone())
+        else {
+            return;
+        };
+
+        let towards = (person.position - car.position).normalized();
+        let speed = car.velocity.dot(towards);
+        let amount = (speed - VEHICLE_HIT_MIN_SPEED).max(0.0) * VEHICLE_HIT_DAMAGE;
+        let source = DamageSource::Vehicle {
+            vehicle_id: vehicle_id.to_string(),
+        };
+        self.damage_person(user_id, amount, source, attacker);
+    }
+
+    // players and pedestrians alike
+    fn damage_person(
+        &mut self,
+        id: &str,
+        amount: f32,
+        source: DamageSource,
+        attacker: Option<String>,
+    ) {
+        if self.players.contains_key(id) {
+            self.damage_player(id, amount, source, attacker);
+        } else {
+            self.damage_pedestrian(id, amount, source, attacker);
+        }
+    }
+
+    pub fn damage_player(
+        &mut self,
+        user_id: &str,
+        amount: f32,
+        source: DamageSource,
+        attacker: Option<String>,
+    ) {
+        let Some(player) = self.players.get_mut(user_id) else {
+            return;
+        };
+        if amount <= 0.0 || !player.is_alive() {
+            return;
+        }
+
+        let pulling_out = player.pull_out.is_some();
+        player.health = (player.health - amount).max(0.0);
+        let died = !player.is_alive();
+        if died {
+            player.respawn_at = Some(self.time + RESPAWN_DELAY);
+        }
+
+        self.events.push(
+            self.tick,
+            self.time,
+            GameEventKind::Damage {
+                target: user_id.to_string(),
+                amount,
+                source: source.clone(),
+                attacker: attacker.clone(),
+            },
+        );
+        if pulling_out {
+            self.interrupt_pull_out(user_id);
+        }
+        // hurting someone else is a crime, whatever it was done with
+        if let Some(attacker) = attacker.as_deref().filter(|attacker| *attacker != user_id) {
+            let crime = match source {
+                DamageSource::Vehicle { .. } => Crime::HitPedestrian,
+                _ => Crime::AttackedPlayer,
+            };
+            self.commit_crime(attacker, crime);
+        }
+
+        if died {
+            // the body drops out of whatever it was riding in, and everything
+            // it was carrying ends up on the ground
+            self.leave_vehicle(user_id).ok();
+            self.drop_inventory(user_id);
+            self.events.push(
+                self.tick,
+                self.time,
+                GameEventKind::Death {
+                    target: user_id.to_string(),
+                    source,
+                    killer: attacker,
+                },
+            );
+        }
+    }
+
+    // one of the map's spawn points at random, or the middle of a map without any
+    fn spawn_point(&mut self) -> Vector {
+        let spawns = &self.map.features().player_spawns;
+        if spawns.is_empty() {
+            return self.map.center();
+        }
+        spawns[self.rng.gen_range(0..spawns.len())]
+    }
+
+    // the cars placed on the map, parked ones belong to whoever the map says
+    // and the rest drive off with the traffic
+    fn spawn_map_vehicles(&mut self) {
+        for spawn in self.map.features().vehicle_spawns.clone() {
+            let id = match &spawn.name {
+                Some(name) => MapFeatures::vehicle_id(name),
+                None => {
+                    self.next_object_id += 1;
+                    format!("vehicle-{}", self.next_object_id)
+                }
+            };
+            let mut vehicle = Vehicle::new(
+                id.clone(),
+                spawn.position,
+                vehicle::VehicleBehavior::Normal,
+                spawn.skin,
+            );
+            vehicle.heading = spawn.heading;
+            if let Some(user_id) = spawn.owner {
+                vehicle.owner = VehicleOwner::Player { user_id };
+            }
+            vehicle.npc_driver = !spawn.parked;
+
+            self.objects.insert(
+                id.clone(),
+                GameObject {
+                    details: GameObjectType::Car(vehicle),
+                },
+            );
+            if !spawn.parked {
+                self.traffic.adopt(id);
+            }
+        }
+    }
+
+    fn spawn_map_items(&mut self) {
+        for spawn in self.map.features().item_spawns.clone() {
+            self.spawn_item(spawn.stack, spawn.position, false);
+        }
+    }
+
+    fn respawn_players(&mut self) {
+        let due: Vec<String> = self
+            .players
+            .values()
+            .filter(|player| player.respawn_at.is_some_and(|at| at <= self.time))
+            .map(|player| player.id.clone())
+            .collect();
+
+        for user_id in due {
+            let Vector { x, y } = self.spawn_point();
+            if let Some(player) = self.players.get_mut(&user_id) {
+                player.respawn(Vector::new(x, y));
+            }
+            self.events.push(
+                self.tick,
+                self.time,
+                GameEventKind::Respawn {
+                    target: user_id,
+                    x,
+                    y,
+                },
+            );
+        }
+    }
+
+    fn entity_snapshot(&self, id: &str) -> Option<EntitySnapshot> {
+        if let Some(player) = self.players.get(id) {
+            return Some(EntitySnapshot {
+                position: player.position(),
+                rotation: player.rotation,
+            });
+        }
+
+        self.objects.get(id).map(|obj| obj.snapshot())
+    }
+
+    fn record_history(&mut self) {
+        let mut entities = HashMap::new();
+        for (id, player) in self.players.iter() {
+            entities.insert(
+                id.clone(),
+                EntitySnapshot {
+                    position: player.position(),
+                    rotation: player.rotation,
+                },
+            );
+        }
+        for (id, obj) in self.objects.iter() {
+            entities.insert(id.clone(), obj.snapshot());
+        }
+
+        self.history.record(HistoryFrame {
+            tick: self.tick,
+            time: self.time,
+            entities,
+        });
+    }
+
+    // where `id` was at the tick the client reports seeing, falling back to its
+    // current position if it isn't in the history buffer
+    pub fn rewound_entity(&self, id: &str, client_tick: Option<u32>) -> Option<EntitySnapshot> {
+        client_tick
+            .and_then(|tick| self.history.rewind(tick))
+            .and_then(|frame| frame.entities.get(id).copied())
+            .or_else(|| self.entity_snapshot(id))
+    }
+
+    pub fn validate_interaction(
+        &self,
+        user_id: &str,
+        object_id: &str,
+        client_tick: Option<u32>,
+    ) -> AppResult<()> {
+        let player = self
+            .players
+            .get(user_id)
+            .ok_or(AppError::BadRequest("Not in this game".to_string()))?;
+        if !player.is_alive() {
+            return Err(AppError::BadRequest("You are dead".to_string()));
+        }
+        let target = self
+            .rewound_entity(object_id, client_tick)
+            .ok_or(AppError::BadRequest("No such object".to_string()))?;
+
+        if (player.position() - target.position).length() > INTERACTION_RANGE {
+            return Err(AppError::BadRequest("Too far away".to_string()));
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -234,10 +1518,14 @@ pub struct Game {
     state: Arc<Mutex<GameState>>,
 }
 
-impl Default for Game {
-    fn default() -> Self {
+impl Game {
+    pub fn new(map: Arc<Map>) -> Self {
+        Self::with_seed(rand::random(), map)
+    }
+
+    pub fn with_seed(seed: u64, map: Arc<Map>) -> Self {
         Self {
-            state: Arc::new(Mutex::new(GameState::default())),
+            state: Arc::new(Mutex::new(GameState::new(seed, map))),
         }
     }
 }
@@ -246,6 +1534,7 @@ pub struct PlayerInput {
     pub x: i32,
     pub y: i32,
     pub rotation: f32,
+    pub controls: Option<DriveControls>,
 }
 
 impl Game {
@@ -254,43 +1543,519 @@ impl Game {
     }
 
     pub async fn input(&mut self, user_id: String, input: PlayerInput) -> &Self {
-        let mut state = self.get_state().lock().await;
-        if let Some(player) = state.players.get_mut(&user_id) {
-            player.input(input)
-        }
+        let command = match input.controls {
+            Some(controls) => GameCommand::Drive(controls),
+            None => GameCommand::Move {
+                x: input.x,
+                y: input.y,
+                rotation: input.rotation,
+            },
+        };
+        self.get_state().lock().await.queue_input(user_id, command);
 
         self
     }
 
-    pub async fn action(&mut self, user_id: String, object_id: String) -> AppResult<&Self> {
+    pub async fn action(
+        &mut self,
+        user_id: String,
+        object_id: String,
+        client_tick: Option<u32>,
+    ) -> AppResult<&Self> {
         let mut state = self.get_state().lock().await;
-
-        if let Some(obj) = state.objects.get_mut(&object_id) {
-            obj.apply_action(Arc::clone(self.get_state()), user_id)
-                .await?;
-        }
+        state.validate_interaction(&user_id, &object_id, client_tick)?;
+        state.queue_input(user_id, GameCommand::Action { object_id });
 
         Ok(self)
     }
 
-    pub async fn tick(&mut self) {
-        let objects = &mut self.state.lock().await.objects;
-        for (id, obj) in objects.iter_mut() {
-            obj.tick().await.expect("hmm");
-        }
+    pub async fn fire(&mut self, user_id: String, aim: Vector, client_tick: Option<u32>) -> &Self {
+        self.get_state()
+            .lock()
+            .await
+            .queue_input(user_id, GameCommand::Fire { aim, client_tick });
+
+        self
+    }
+
+    pub async fn inventory(&mut self, user_id: String, action: InventoryAction) -> &Self {
+        self.get_state()
+            .lock()
+            .await
+            .queue_input(user_id, GameCommand::Inventory(action));
+
+        self
+    }
+
+    pub async fn switch_seat(&mut self, user_id: String, seat: Seat) -> &Self {
+        self.get_state()
+            .lock()
+            .await
+            .queue_input(user_id, GameCommand::SwitchSeat { seat });
+
+        self
+    }
+
+    pub async fn tick(&mut self, dt: Duration) {
+        self.state.lock().await.step(dt);
     }
 }
 
-#[async_trait]
 pub trait Controllable {
-    async fn control(&mut self, player: &Player, state: Arc<Mutex<GameState>>) -> AppResult<()>;
+    fn take_control(&mut self, user_id: &str);
+    fn release_control(&mut self);
+    fn control(&mut self, controls: DriveControls);
 }
 
-#[async_trait]
-impl Controllable for CarDetails {
-    async fn control(&mut self, player: &Player, _state: Arc<Mutex<GameState>>) -> AppResult<()> {
-        println!("Player {} is now controlling the car", player.id);
-        self.driver_user_id = Some(player.id.clone());
-        Ok(())
+mod test {
+    use std::time::Duration;
+
+    use super::{
+        events::{DamageSource, GameEventKind},
+        input_log::GameCommand,
+        inventory::{InventoryAction, ItemKind},
+        map::RoadType,
+        map_registry::MapRegistry,
+        police::Crime,
+        vehicle::{Seat, Vehicle, VehicleBehavior},
+        weapon::WeaponKind,
+        CarSkin, GameState, MAX_HEALTH,
+    };
+
+    fn play(state: &mut GameState) {
+        for tick in 0..100 {
+            if tick % 10 == 0 {
+                state.queue_input(
+                    "tim".to_string(),
+                    GameCommand::Move {
+                        x: 600 + tick,
+                        y: 800 - tick,
+                        rotation: tick as f32 * 0.1,
+                    },
+                );
+            }
+            if tick == 50 {
+                state.queue_input(
+                    "bob".to_string(),
+                    GameCommand::Action {
+                        object_id: "map-tim's car".to_string(),
+                    },
+                );
+            }
+            state.step(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn test() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut first = GameState::new(42, map.clone());
+        let mut second = GameState::new(42, map.clone());
+        play(&mut first);
+        play(&mut second);
+
+        let latest = first.history.latest().unwrap();
+        assert_eq!(latest.entities, second.history.latest().unwrap().entities);
+
+        let replayed = GameState::replay(&first.input_log, map);
+        assert_eq!(replayed.tick, first.tick);
+        assert_eq!(replayed.input_log, first.input_log);
+        assert_eq!(replayed.history.latest().unwrap().entities, latest.entities);
+    }
+
+    #[test]
+    fn zones() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(1, map);
+        let zone = |state: &GameState| match state.players["tim"].to_outgoing_game_object().details
+        {
+            super::GameObjectInfo::Person(person) => person.zone,
+            _ => None,
+        };
+
+        state.step(Duration::from_millis(50));
+        assert_eq!(zone(&state).as_deref(), Some("home"));
+        state
+            .players
+            .get_mut("tim")
+            .unwrap()
+            .set_position(super::Vector::new(160.0, 368.0));
+        state.step(Duration::from_millis(50));
+        assert_eq!(zone(&state), None);
+    }
+
+    #[test]
+    fn rotation() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(1, map);
+        for rotation in [1e30, 1e6, f32::NAN, f32::INFINITY, -7.0] {
+            state.queue_input(
+                "tim".to_string(),
+                GameCommand::Move {
+                    x: 600,
+                    y: 800,
+                    rotation,
+                },
+            );
+            state.step(Duration::from_millis(50));
+            let tim = &state.players["tim"];
+            assert!(tim.rotation.is_finite() && tim.rotation.abs() <= std::f32::consts::PI);
+        }
+        let expected = -7.0 + std::f32::consts::TAU;
+        assert!((state.players["tim"].rotation - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn seats() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(1, map);
+        let car = "map-tim's car";
+        state.vehicle_mut(car).unwrap().max_passengers = 1;
+
+        state.enter_vehicle("tim", car).unwrap();
+        state.enter_vehicle("bob", car).unwrap();
+        assert_eq!(
+            state.vehicle(car).unwrap().driver_user_id.as_deref(),
+            Some("tim")
+        );
+        assert_eq!(state.vehicle(car).unwrap().passenger_user_ids, vec!["bob"]);
+        assert!(state.switch_seat("bob", Seat::Driver).is_err());
+
+        state.exit_vehicle("tim").unwrap();
+        state.switch_seat("bob", Seat::Driver).unwrap();
+        assert_eq!(
+            state.vehicle(car).unwrap().driver_user_id.as_deref(),
+            Some("bob")
+        );
+        assert!(state.vehicle(car).unwrap().passenger_user_ids.is_empty());
+
+        state.enter_vehicle("tim", car).unwrap();
+        state.step(Duration::from_millis(50));
+        let position = state.vehicle(car).unwrap().position;
+        assert_eq!(
+            state.players["tim"].position(),
+            position.to_coordinates().into()
+        );
+
+        state.destroy_vehicle(car).unwrap();
+        assert!(state
+            .players
+            .values()
+            .all(|player| player.vehicle_id.is_none()));
+    }
+
+    #[test]
+    fn health() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(3, map);
+        let car = "map-tim's car";
+
+        // bailing out at 220px/s is (220 - 60) * 0.4 damage
+        state.enter_vehicle("tim", car).unwrap();
+        state.vehicle_mut(car).unwrap().speed = 220.0;
+        state.exit_vehicle("tim").unwrap();
+        assert_eq!(state.players["tim"].health, MAX_HEALTH - 64.0);
+
+        let source = DamageSource::Vehicle {
+            vehicle_id: car.to_string(),
+        };
+        state.damage_player("tim", 50.0, source.clone(), Some("bob".to_string()));
+        assert!(!state.players["tim"].is_alive());
+        let kill = state.events.kill_feed().last().unwrap();
+        assert_eq!(kill.victim, "tim");
+        assert_eq!(kill.killer.as_deref(), Some("bob"));
+        assert_eq!(kill.source, source);
+
+        // dead players can't be hurt again or act
+        state.damage_player("tim", 10.0, DamageSource::Fall, None);
+        assert_eq!(state.players["tim"].health, 0.0);
+        assert!(state.validate_interaction("tim", car, None).is_err());
+
+        for _ in 0..100 {
+            state.step(Duration::from_millis(50));
+        }
+        assert_eq!(state.players["tim"].health, MAX_HEALTH);
+        assert!(state.events.recent().any(
+            |event| matches!(&event.kind, GameEventKind::Respawn { target, .. } if target == "tim")
+        ));
+    }
+
+    #[test]
+    fn wrecks() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(4, map);
+        let car = "map-tim's car";
+        state.enter_vehicle("tim", car).unwrap();
+        let position = state.vehicle(car).unwrap().position;
+        state
+            .players
+            .get_mut("bob")
+            .unwrap()
+            .set_position(position + super::Vector::new(0.0, 30.0));
+
+        state
+            .vehicle_mut(car)
+            .unwrap()
+            .take_damage(1000.0, Some("bob".to_string()));
+        state.step(Duration::from_millis(50));
+
+        assert!(state.vehicle(car).unwrap().is_wrecked());
+        assert!(state.enter_vehicle("tim", car).is_err());
+        assert!(state.players["tim"].vehicle_id.is_none());
+        assert!(state.players["tim"].health < MAX_HEALTH);
+        assert!(state.players["bob"].health < MAX_HEALTH);
+        assert!(state.events.recent().any(|event| matches!(
+            &event.kind,
+            GameEventKind::Damage { target, attacker: Some(attacker), .. }
+                if target == "tim" && attacker == "bob"
+        )));
+
+        for _ in 0..600 {
+            state.step(Duration::from_millis(50));
+        }
+        assert!(state.vehicle(car).is_none());
+    }
+
+    #[test]
+    fn combat() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(5, map);
+        let right = super::Vector::new(1.0, 0.0);
+        state
+            .players
+            .get_mut("tim")
+            .unwrap()
+            .set_position(super::Vector::new(500.0, 800.0));
+        state
+            .players
+            .get_mut("bob")
+            .unwrap()
+            .set_position(super::Vector::new(600.0, 800.0));
+
+        for aim in [
+            super::Vector::ZERO,
+            super::Vector::new(f32::NAN, 0.0),
+            super::Vector::new(f32::INFINITY, 1.0),
+            super::Vector::new(f32::MAX, f32::MAX),
+        ] {
+            assert!(state.fire("tim", aim, None).is_err());
+        }
+        state.fire("tim", right, None).unwrap();
+        assert_eq!(state.players["bob"].health, MAX_HEALTH - 20.0);
+        assert!(state.events.recent().any(|event| matches!(
+            &event.kind,
+            GameEventKind::Hit { target: Some(target), x, .. } if target == "bob" && *x == 594.0
+        )));
+        assert!(state.fire("tim", right, None).is_err());
+        // swapping weapons doesn't reset the cooldown
+        for weapon in [WeaponKind::Melee, WeaponKind::Pistol] {
+            state
+                .inventory_action("tim", InventoryAction::Equip { weapon })
+                .unwrap();
+        }
+        assert!(state.fire("tim", right, None).is_err());
+        assert_eq!(state.players["bob"].health, MAX_HEALTH - 20.0);
+
+        // firing away from bob misses him
+        state.step(Duration::from_millis(500));
+        state.fire("tim", right * -1.0, None).unwrap();
+        assert_eq!(state.players["bob"].health, MAX_HEALTH - 20.0);
+        let ammo = ItemKind::Ammo(WeaponKind::Pistol);
+        assert_eq!(state.players["tim"].inventory.count(ammo), 34);
+    }
+
+    #[test]
+    fn police() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(7, map);
+        state
+            .players
+            .get_mut("tim")
+            .unwrap()
+            .set_position(super::Vector::new(500.0, 800.0));
+        state
+            .players
+            .get_mut("bob")
+            .unwrap()
+            .set_position(super::Vector::new(600.0, 800.0));
+
+        state
+            .fire("tim", super::Vector::new(1.0, 0.0), None)
+            .unwrap();
+        assert_eq!(state.players["tim"].wanted.level(), 1);
+        assert!(state.events.recent().any(|event| matches!(
+            &event.kind,
+            GameEventKind::WantedLevel { target, level: 1 } if target == "tim"
+        )));
+
+        state.step(Duration::from_millis(50));
+        assert_eq!(state.police.len(), 1);
+        let (unit_id, unit) = state.police.iter().next().unwrap();
+        assert_eq!(unit.suspect, "tim");
+        let unit_id = unit_id.clone();
+        let car = state.vehicle(&unit_id).unwrap();
+        assert_eq!(car.skin, CarSkin::Police);
+        assert!(!car.path.is_empty());
+
+        // dying clears the slate and sends the units home, driving off with
+        // the traffic rather than disappearing in front of bob
+        state.damage_player("tim", 500.0, DamageSource::Fall, None);
+        state.step(Duration::from_millis(50));
+        assert_eq!(state.players["tim"].wanted.level(), 0);
+        assert!(state.police.is_empty());
+        assert!(state.vehicle(&unit_id).is_some());
+        assert!(state.traffic.vehicles().any(|id| *id == unit_id));
+
+        // and gets cleared away once nobody is around to see it
+        for user_id in ["tim", "bob"] {
+            state
+                .players
+                .get_mut(user_id)
+                .unwrap()
+                .set_position(super::Vector::new(-100_000.0, -100_000.0));
+        }
+        state.step(Duration::from_millis(50));
+        assert!(state.vehicle(&unit_id).is_none());
+    }
+
+    #[test]
+    fn pursuit() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(7, map);
+        for _ in 0..3 {
+            state.commit_crime("tim", Crime::AttackedPolice);
+        }
+
+        // units go flat out after the suspect, whatever the road says
+        let mut fastest: f32 = 0.0;
+        for _ in 0..100 {
+            state.step(Duration::from_millis(50));
+            for id in state.police.keys() {
+                let car = state.vehicle(id).unwrap();
+                assert_eq!(car.speed_limit, None);
+                fastest = fastest.max(car.speed);
+            }
+        }
+        assert_eq!(state.police.len(), 3);
+        assert!(fastest > RoadType::Local.speed_limit());
+    }
+
+    #[test]
+    fn theft() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(8, map);
+        let car = "traffic";
+        let position = super::Vector::new(400.0, 800.0);
+        state.objects.insert(
+            car.to_string(),
+            super::GameObject {
+                details: super::GameObjectType::Car(Vehicle::new(
+                    car.to_string(),
+                    position,
+                    VehicleBehavior::Normal,
+                    CarSkin::Sedan,
+                )),
+            },
+        );
+        let bob = state.players.get_mut("bob").unwrap();
+        bob.set_position(position + super::Vector::new(0.0, 20.0));
+
+        // walking off lets go of the driver
+        state.enter_vehicle("bob", car).unwrap();
+        assert!(state.players["bob"].vehicle_id.is_none());
+        state
+            .players
+            .get_mut("bob")
+            .unwrap()
+            .set_position(position + super::Vector::new(0.0, 30.0));
+        state.step(Duration::from_millis(50));
+        assert!(state.players["bob"].pull_out.is_none());
+        assert!(state.events.recent().any(|event| matches!(
+            &event.kind,
+            GameEventKind::PullOutInterrupted { thief, .. } if thief == "bob"
+        )));
+
+        state.enter_vehicle("bob", car).unwrap();
+        for _ in 0..30 {
+            state.step(Duration::from_millis(50));
+        }
+        let vehicle = state.vehicle(car).unwrap();
+        assert_eq!(vehicle.driver_user_id.as_deref(), Some("bob"));
+        assert!(!vehicle.npc_driver);
+        assert_eq!(state.players["bob"].vehicle_id.as_deref(), Some(car));
+        assert_eq!(state.players["bob"].wanted.level(), 1);
+
+        // getting back into a car you already stole isn't another theft
+        state.exit_vehicle("bob").unwrap();
+        state.enter_vehicle("bob", car).unwrap();
+        assert_eq!(state.players["bob"].vehicle_id.as_deref(), Some(car));
+        assert_eq!(state.players["bob"].wanted.level(), 1);
+        let thefts = state
+            .events
+            .recent()
+            .filter(|event| matches!(&event.kind, GameEventKind::VehicleStolen { thief, .. } if thief == "bob"))
+            .count();
+        assert_eq!(thefts, 1);
+
+        // tim's own car doesn't count
+        state.enter_vehicle("tim", "map-tim's car").unwrap();
+        assert_eq!(state.players["tim"].wanted.level(), 0);
+    }
+
+    #[test]
+    fn items() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(6, map);
+        let rifle = ItemKind::Weapon(WeaponKind::Rifle);
+        let item_id = |state: &GameState, kind: ItemKind| {
+            state
+                .objects
+                .iter()
+                .find_map(|(id, obj)| match &obj.details {
+                    super::GameObjectType::Item(item) if item.stack.kind == kind => {
+                        Some(id.clone())
+                    }
+                    _ => None,
+                })
+        };
+
+        let id = item_id(&state, rifle).unwrap();
+        state.pick_up("tim", &id).unwrap();
+        assert!(!state.objects.contains_key(&id));
+        state
+            .inventory_action(
+                "tim",
+                InventoryAction::Equip {
+                    weapon: WeaponKind::Rifle,
+                },
+            )
+            .unwrap();
+        assert!(state
+            .inventory_action(
+                "bob",
+                InventoryAction::Equip {
+                    weapon: WeaponKind::Rifle
+                }
+            )
+            .is_err());
+
+        state.damage_player("tim", 500.0, DamageSource::Fall, None);
+        let tim = &state.players["tim"];
+        assert!(tim.inventory.slots.is_empty());
+        assert_eq!(tim.weapon.kind, WeaponKind::Melee);
+        // pistol, pistol ammo and the rifle are back on the ground
+        let dropped = state
+            .objects
+            .values()
+            .filter(|obj| matches!(&obj.details, super::GameObjectType::Item(item) if item.despawn_at.is_some()))
+            .count();
+        assert_eq!(dropped, 3);
+        assert!(item_id(&state, rifle).is_some());
+
+        for _ in 0..1300 {
+            state.step(Duration::from_millis(50));
+        }
+        assert!(item_id(&state, rifle).is_none());
+        assert!(item_id(&state, ItemKind::Medkit).is_some());
     }
 }
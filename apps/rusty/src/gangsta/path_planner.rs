@@ -0,0 +1,377 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::Duration,
+};
+
+use super::{
+    map::{tile_center, Coordinates},
+    pathfinding::Congestion,
+    vector::Vector,
+    GameState,
+};
+
+// A* searches run per tick, anything past that waits for the next one
+const SEARCHES_PER_TICK: usize = 8;
+// routes longer than this many tiles go from junction to junction
+const LONG_ROUTE: i32 = 40;
+// long routes are cached by the block of tiles they start and end in
+const REGION_SIZE: i32 = 8;
+// cached routes were planned around the traffic back then
+const CACHE_TTL: Duration = Duration::from_secs(10);
+const MAX_CACHED_ROUTES: usize = 128;
+
+// what to do with the path once it's found
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathPurpose {
+    Traffic,
+    // drive the roads, then go straight for whoever is being chased
+    Pursuit { target: Vector },
+}
+
+#[derive(Debug, Clone)]
+struct PathRequest {
+    vehicle_id: String,
+    purpose: PathPurpose,
+    route: RouteJob,
+}
+
+// one piece of a route, only the searches count against the tick's budget
+#[derive(Debug, Clone)]
+enum Leg {
+    // search from wherever the route has got to so far
+    To(Coordinates),
+    // tiles taken from the cache, starting where the route has got to
+    Known(Vec<Coordinates>),
+    // the junction to junction part starts and ends here
+    MiddleStart,
+    MiddleEnd((Coordinates, Coordinates)),
+}
+
+// a route being planned, possibly over several ticks
+#[derive(Debug, Clone)]
+struct RouteJob {
+    start: Coordinates,
+    goal: Coordinates,
+    // worked out when the job is first picked up, by which time the cache
+    // may already have the middle part
+    legs: Option<VecDeque<Leg>>,
+    tiles: Vec<Coordinates>,
+    middle_start: usize,
+    // the long way round failed and the goal is searched for directly
+    direct: bool,
+    failed: bool,
+}
+
+impl RouteJob {
+    fn new(start: Coordinates, goal: Coordinates) -> Self {
+        Self {
+            start,
+            goal,
+            legs: None,
+            tiles: vec![start],
+            middle_start: 0,
+            direct: false,
+            failed: false,
+        }
+    }
+
+    fn fall_back(&mut self) {
+        self.legs = Some(VecDeque::from([Leg::To(self.goal)]));
+        self.tiles = vec![self.start];
+        self.direct = true;
+    }
+
+    fn result(self) -> Option<Vec<Coordinates>> {
+        (!self.failed).then_some(self.tiles)
+    }
+}
+
+// the junction to junction part of a long route
+#[derive(Debug, Clone)]
+struct CachedRoute {
+    tiles: Vec<Coordinates>,
+    planned_at: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct PathPlanner {
+    queue: VecDeque<PathRequest>,
+    cache: BTreeMap<(Coordinates, Coordinates), CachedRoute>,
+}
+
+impl PathPlanner {
+    pub fn is_pending(&self, vehicle_id: &str) -> bool {
+        self.queue
+            .iter()
+            .any(|request| request.vehicle_id == vehicle_id)
+    }
+
+    pub fn cancel(&mut self, vehicle_id: &str) {
+        self.queue
+            .retain(|request| request.vehicle_id != vehicle_id);
+    }
+
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn cached_routes(&self) -> usize {
+        self.cache.len()
+    }
+
+    fn cached(&self, key: &(Coordinates, Coordinates), now: Duration) -> Option<&CachedRoute> {
+        self.cache
+            .get(key)
+            .filter(|route| now < route.planned_at + CACHE_TTL)
+    }
+
+    fn store(&mut self, key: (Coordinates, Coordinates), tiles: Vec<Coordinates>, now: Duration) {
+        self.cache
+            .retain(|_, route| now < route.planned_at + CACHE_TTL);
+        if self.cache.len() >= MAX_CACHED_ROUTES {
+            let oldest = self
+                .cache
+                .iter()
+                .min_by_key(|(_, route)| route.planned_at)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.cache.remove(&oldest);
+            }
+        }
+        self.cache.insert(
+            key,
+            CachedRoute {
+                tiles,
+                planned_at: now,
+            },
+        );
+    }
+}
+
+impl GameState {
+    // queues a search, replacing whatever the vehicle was still waiting for
+    pub(super) fn request_path(
+        &mut self,
+        vehicle_id: &str,
+        start: Coordinates,
+        goal: Coordinates,
+        purpose: PathPurpose,
+    ) {
+        self.paths.cancel(vehicle_id);
+        self.paths.queue.push_back(PathRequest {
+            vehicle_id: vehicle_id.to_string(),
+            purpose,
+            route: RouteJob::new(start, goal),
+        });
+    }
+
+    // works through the queue oldest first, running at most SEARCHES_PER_TICK
+    // searches. a route that runs out of budget halfway stays at the front
+    // and carries on next tick
+    pub(super) fn update_paths(&mut self) {
+        if self.paths.queue.is_empty() {
+            return;
+        }
+
+        let congestion = self.congestion();
+        let mut budget = SEARCHES_PER_TICK;
+        while budget > 0 {
+            let Some(mut request) = self.paths.queue.pop_front() else {
+                break;
+            };
+            if !self.advance_route(&mut request.route, &congestion, &mut budget) {
+                self.paths.queue.push_front(request);
+                break;
+            }
+            let tiles = request.route.result();
+
+            let Some(car) = self.vehicle_mut(&request.vehicle_id) else {
+                continue;
+            };
+            // a player took the wheel while the car was waiting
+            if car.driver_user_id.is_some() {
+                continue;
+            }
+            match request.purpose {
+                PathPurpose::Traffic => {
+                    if let Some(tiles) = tiles {
+                        car.set_tile_path(tiles);
+                    }
+                }
+                PathPurpose::Pursuit { target } => {
+                    // the first tile is the one the car is already on
+                    let mut path: Vec<Vector> = tiles
+                        .into_iter()
+                        .flatten()
+                        .skip(1)
+                        .map(tile_center)
+                        .collect();
+                    path.push(target);
+                    car.set_path(path);
+                }
+            }
+        }
+    }
+
+    // runs the route's legs until it's done or the budget is spent, true
+    // once there's nothing left to do
+    fn advance_route(
+        &mut self,
+        route: &mut RouteJob,
+        congestion: &Congestion,
+        budget: &mut usize,
+    ) -> bool {
+        if route.legs.is_none() {
+            route.legs = Some(self.plan_legs(route.start, route.goal));
+        }
+
+        while let Some(leg) = route.legs.as_mut().and_then(|legs| legs.pop_front()) {
+            match leg {
+                Leg::To(target) => {
+                    if *budget == 0 {
+                        if let Some(legs) = route.legs.as_mut() {
+                            legs.push_front(leg);
+                        }
+                        return false;
+                    }
+                    *budget -= 1;
+
+                    let from = *route.tiles.last().unwrap_or(&route.start);
+                    match self.map.find_path_with(from, target, Some(congestion)) {
+                        // every part starts on the tile the one before it ended on
+                        Some(path) => route.tiles.extend(path.into_iter().skip(1)),
+                        None if route.direct => {
+                            route.failed = true;
+                            return true;
+                        }
+                        None => route.fall_back(),
+                    }
+                }
+                Leg::Known(tiles) => route.tiles.extend(tiles.into_iter().skip(1)),
+                Leg::MiddleStart => route.middle_start = route.tiles.len() - 1,
+                Leg::MiddleEnd(key) => {
+                    let middle = route.tiles[route.middle_start..].to_vec();
+                    self.paths.store(key, middle, self.time);
+                }
+            }
+        }
+
+        true
+    }
+
+    // short routes are searched directly, long ones go through the junction
+    // graph and reuse the middle part from the cache where they can
+    fn plan_legs(&self, start: Coordinates, goal: Coordinates) -> VecDeque<Leg> {
+        let direct = VecDeque::from([Leg::To(goal)]);
+        if (goal.x - start.x).abs() + (goal.y - start.y).abs() < LONG_ROUTE {
+            return direct;
+        }
+
+        let key = (region(start), region(goal));
+        if let Some(route) = self.paths.cached(&key, self.time) {
+            let Some(first) = route.tiles.first() else {
+                return direct;
+            };
+            return VecDeque::from([
+                Leg::To(*first),
+                Leg::Known(route.tiles.clone()),
+                Leg::To(goal),
+            ]);
+        }
+
+        // tile by tile from the junction nearest the start to the one
+        // nearest the goal
+        let junctions = self.map.junctions();
+        let (Some(from), Some(to)) = (junctions.nearest(start), junctions.nearest(goal)) else {
+            return direct;
+        };
+        if from == to {
+            return direct;
+        }
+        let Some(route) = junctions.route(from, to) else {
+            return direct;
+        };
+
+        let mut legs = VecDeque::from([Leg::To(junctions.node(from)), Leg::MiddleStart]);
+        legs.extend(
+            route
+                .iter()
+                .skip(1)
+                .map(|node| Leg::To(junctions.node(*node))),
+        );
+        legs.push_back(Leg::MiddleEnd(key));
+        legs.push_back(Leg::To(goal));
+        legs
+    }
+}
+
+fn region(tile: Coordinates) -> Coordinates {
+    Coordinates {
+        x: tile.x.div_euclid(REGION_SIZE),
+        y: tile.y.div_euclid(REGION_SIZE),
+    }
+}
+
+mod test {
+    use crate::gangsta::{
+        map::Coordinates,
+        map_registry::MapRegistry,
+        path_planner::{PathPurpose, RouteJob, SEARCHES_PER_TICK},
+        GameState,
+    };
+
+    #[test]
+    fn test() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(3, map);
+        let tile = |x, y| Coordinates { x, y };
+        let congestion = state.congestion();
+
+        // along the main road, far enough to go junction to junction
+        let mut plan = |start, goal| {
+            let (mut route, mut budget) = (RouteJob::new(start, goal), usize::MAX);
+            state.advance_route(&mut route, &congestion, &mut budget);
+            route.result().unwrap()
+        };
+        let first = plan(tile(10, 23), tile(60, 23));
+        let second = plan(tile(11, 23), tile(60, 23));
+        assert_eq!(state.paths.cached_routes(), 1);
+        for (path, start) in [(&first, tile(10, 23)), (&second, tile(11, 23))] {
+            assert_eq!(path.first(), Some(&start));
+            assert_eq!(path.last(), Some(&tile(60, 23)));
+            assert!(path
+                .windows(2)
+                .all(|pair| (pair[0].x - pair[1].x).abs() + (pair[0].y - pair[1].y).abs() == 1));
+        }
+
+        // more requests than a tick's budget, the rest wait their turn
+        let pending = state.paths.pending();
+        for index in 0..SEARCHES_PER_TICK + 3 {
+            let id = format!("car-{index}");
+            state.request_path(&id, tile(10, 23), tile(20, 23), PathPurpose::Traffic);
+        }
+        state.request_path("car-0", tile(10, 23), tile(30, 23), PathPurpose::Traffic);
+        assert_eq!(state.paths.pending(), pending + SEARCHES_PER_TICK + 3);
+        state.update_paths();
+        assert_eq!(state.paths.pending(), pending + 3);
+        assert!(state.paths.is_pending("car-0"));
+        while state.paths.pending() > 0 {
+            state.update_paths();
+        }
+
+        // the budget counts searches, a long route nobody has driven yet
+        // takes several. it gets as far as it can behind the short ones and
+        // finishes next tick
+        for index in 0..SEARCHES_PER_TICK - 1 {
+            let id = format!("car-{index}");
+            state.request_path(&id, tile(10, 23), tile(20, 23), PathPurpose::Traffic);
+        }
+        state.request_path("long", tile(15, 24), tile(60, 24), PathPurpose::Traffic);
+        state.update_paths();
+        assert_eq!(state.paths.pending(), 1);
+        assert!(state.paths.is_pending("long"));
+        state.update_paths();
+        assert_eq!(state.paths.pending(), 0);
+        assert_eq!(state.paths.cached_routes(), 2);
+    }
+}
@@ -0,0 +1,376 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use super::{
+    map::{pixel_to_tile, Coordinates, Intersection, Map},
+    road_graph::{Direction, RoadGraph},
+    GameObjectType, GameState,
+};
+
+// extra cost of driving onto a tile another car is on
+const CONGESTION_PENALTY: i32 = 8;
+// a wreck blocks the lane until it's cleared away
+const WRECK_PENALTY: i32 = 40;
+const NO_TILE: usize = usize::MAX;
+const NO_JUNCTION: usize = usize::MAX;
+// on foot the pavement is quickest, open ground will do and roads are only
+// crossed when there's no way round
+const SIDEWALK_COST: i32 = 1;
+const GROUND_COST: i32 = 3;
+const CROSSING_COST: i32 = 8;
+
+// extra cost per tile on top of the road graph, laid out like it
+#[derive(Debug, Clone)]
+pub struct Congestion {
+    width: usize,
+    penalties: Vec<i32>,
+}
+
+impl Congestion {
+    pub fn new(map: &Map) -> Self {
+        let roads = map.roads();
+        Self {
+            width: roads.width(),
+            penalties: vec![0; roads.width() * roads.height()],
+        }
+    }
+
+    pub fn add(&mut self, tile: Coordinates, penalty: i32) {
+        if tile.x < 0 || tile.y < 0 || tile.x as usize >= self.width {
+            return;
+        }
+        if let Some(cost) = self
+            .penalties
+            .get_mut(tile.y as usize * self.width + tile.x as usize)
+        {
+            *cost += penalty;
+        }
+    }
+
+    fn penalty(&self, index: usize) -> i32 {
+        self.penalties[index]
+    }
+}
+
+// the junctions and the cheapest drive between neighbouring ones, for
+// planning long routes without searching every tile in between
+#[derive(Debug, Clone, Default)]
+pub struct JunctionGraph {
+    // the tile in the middle of each junction
+    nodes: Vec<Coordinates>,
+    edges: Vec<Vec<(usize, i32)>>,
+}
+
+impl JunctionGraph {
+    pub fn new(map: &Map) -> Self {
+        let roads = map.roads();
+        let intersections = map.intersections();
+        let mut junction_of = vec![NO_JUNCTION; roads.width() * roads.height()];
+        for (junction, Intersection { min, max }) in intersections.iter().enumerate() {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    if let Some(index) = roads.index(Coordinates { x, y }) {
+                        junction_of[index] = junction;
+                    }
+                }
+            }
+        }
+        let nodes: Vec<Coordinates> = intersections
+            .iter()
+            .map(|intersection| pixel_to_tile(intersection.center().to_coordinates()))
+            .collect();
+
+        // searches out from every junction, stopping at the middle of the
+        // next ones it runs into
+        let mut edges = vec![];
+        for (junction, node) in nodes.iter().enumerate() {
+            let mut junction_edges = vec![];
+            let mut costs = vec![i32::MAX; junction_of.len()];
+            let mut open = BinaryHeap::new();
+            if let Some(start) = roads.index(*node) {
+                costs[start] = 0;
+                open.push(Reverse((0, start)));
+            }
+            while let Some(Reverse((cost, index))) = open.pop() {
+                if cost > costs[index] {
+                    continue;
+                }
+                let here = junction_of[index];
+                if here != NO_JUNCTION && here != junction && nodes[here] == roads.tile(index) {
+                    junction_edges.push((here, cost));
+                    continue;
+                }
+                for (neighbour, step) in roads.neighbours(roads.tile(index)) {
+                    let Some(next) = roads.index(neighbour) else {
+                        continue;
+                    };
+                    // once inside another junction only its middle is of interest
+                    if here != NO_JUNCTION && here != junction && junction_of[next] != here {
+                        continue;
+                    }
+                    if cost + step < costs[next] {
+                        costs[next] = cost + step;
+                        open.push(Reverse((cost + step, next)));
+                    }
+                }
+            }
+            edges.push(junction_edges);
+        }
+
+        Self { nodes, edges }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, junction: usize) -> Coordinates {
+        self.nodes[junction]
+    }
+
+    pub fn nearest(&self, tile: Coordinates) -> Option<usize> {
+        (0..self.nodes.len()).min_by_key(|junction| {
+            let node = self.nodes[*junction];
+            (node.x - tile.x).abs() + (node.y - tile.y).abs()
+        })
+    }
+
+    // the junctions to pass through on the cheapest drive between two of them
+    pub fn route(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut costs = vec![i32::MAX; self.nodes.len()];
+        let mut came_from = vec![NO_JUNCTION; self.nodes.len()];
+        let mut open = BinaryHeap::new();
+        costs[from] = 0;
+        open.push(Reverse((0, from)));
+
+        while let Some(Reverse((cost, junction))) = open.pop() {
+            if junction == to {
+                let mut route = vec![to];
+                let mut current = to;
+                while came_from[current] != NO_JUNCTION {
+                    current = came_from[current];
+                    route.push(current);
+                }
+                route.reverse();
+                return Some(route);
+            }
+            if cost > costs[junction] {
+                continue;
+            }
+            for &(next, step) in &self.edges[junction] {
+                if cost + step < costs[next] {
+                    costs[next] = cost + step;
+                    came_from[next] = junction;
+                    open.push(Reverse((cost + step, next)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Map {
+    pub fn find_path(&self, start: Coordinates, goal: Coordinates) -> Option<Vec<Coordinates>> {
+        self.find_path_with(start, goal, None)
+    }
+
+    // A* over the road graph, only moves the lanes allow so paths keep to the
+    // right, and the cheapest route rather than the shortest one
+    pub fn find_path_with(
+        &self,
+        start: Coordinates,
+        goal: Coordinates,
+        congestion: Option<&Congestion>,
+    ) -> Option<Vec<Coordinates>> {
+        let roads = self.roads();
+        let (start_index, goal_index) = (roads.index(start)?, roads.index(goal)?);
+        let heuristic = |tile: Coordinates| {
+            ((tile.x - goal.x).abs() + (tile.y - goal.y).abs()) * RoadGraph::min_cost()
+        };
+
+        let mut costs = vec![i32::MAX; roads.width() * roads.height()];
+        let mut came_from = vec![NO_TILE; costs.len()];
+        let mut open = BinaryHeap::new();
+        costs[start_index] = 0;
+        open.push(Reverse((heuristic(start), 0, start_index)));
+
+        while let Some(Reverse((_, cost, index))) = open.pop() {
+            if index == goal_index {
+                let mut path = vec![goal];
+                let mut current = index;
+                while came_from[current] != NO_TILE {
+                    current = came_from[current];
+                    path.push(roads.tile(current));
+                }
+                path.reverse();
+                return Some(path);
+            }
+            // already reached more cheaply
+            if cost > costs[index] {
+                continue;
+            }
+
+            for (neighbour, step) in roads.neighbours(roads.tile(index)) {
+                let Some(next) = roads.index(neighbour) else {
+                    continue;
+                };
+                let next_cost = cost + step + congestion.map_or(0, |c| c.penalty(next));
+                if next_cost < costs[next] {
+                    costs[next] = next_cost;
+                    came_from[next] = index;
+                    open.push(Reverse((next_cost + heuristic(neighbour), next_cost, next)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Map {
+    // walkable ground next to a road
+    pub fn is_sidewalk(&self, tile: Coordinates) -> bool {
+        if !self.is_walkable(tile) || self.is_road(tile) {
+            return false;
+        }
+        (-1..=1).any(|dy| {
+            (-1..=1).any(|dx| {
+                self.is_road(Coordinates {
+                    x: tile.x + dx,
+                    y: tile.y + dy,
+                })
+            })
+        })
+    }
+
+    fn walk_cost(&self, tile: Coordinates) -> Option<i32> {
+        if !self.is_walkable(tile) {
+            None
+        } else if self.is_road(tile) {
+            Some(CROSSING_COST)
+        } else if self.is_sidewalk(tile) {
+            Some(SIDEWALK_COST)
+        } else {
+            Some(GROUND_COST)
+        }
+    }
+
+    // A* for people on foot, ignoring lanes but keeping to the pavement
+    pub fn find_walk_path(
+        &self,
+        start: Coordinates,
+        goal: Coordinates,
+    ) -> Option<Vec<Coordinates>> {
+        let roads = self.roads();
+        let (start_index, goal_index) = (roads.index(start)?, roads.index(goal)?);
+        let heuristic =
+            |tile: Coordinates| ((tile.x - goal.x).abs() + (tile.y - goal.y).abs()) * SIDEWALK_COST;
+
+        let mut costs = vec![i32::MAX; roads.width() * roads.height()];
+        let mut came_from = vec![NO_TILE; costs.len()];
+        let mut open = BinaryHeap::new();
+        costs[start_index] = 0;
+        open.push(Reverse((heuristic(start), 0, start_index)));
+
+        while let Some(Reverse((_, cost, index))) = open.pop() {
+            if index == goal_index {
+                let mut path = vec![goal];
+                let mut current = index;
+                while came_from[current] != NO_TILE {
+                    current = came_from[current];
+                    path.push(roads.tile(current));
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if cost > costs[index] {
+                continue;
+            }
+
+            let tile = roads.tile(index);
+            for direction in Direction::ALL {
+                let (dx, dy) = direction.offset();
+                let neighbour = Coordinates {
+                    x: tile.x + dx,
+                    y: tile.y + dy,
+                };
+                let (Some(next), Some(step)) = (roads.index(neighbour), self.walk_cost(neighbour))
+                else {
+                    continue;
+                };
+                if cost + step < costs[next] {
+                    costs[next] = cost + step;
+                    came_from[next] = index;
+                    open.push(Reverse((
+                        cost + step + heuristic(neighbour),
+                        cost + step,
+                        next,
+                    )));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl GameState {
+    // where the cars are right now, so routes steer around jams and wrecks
+    pub(super) fn congestion(&self) -> Congestion {
+        let mut congestion = Congestion::new(&self.map);
+        for obj in self.objects.values() {
+            let GameObjectType::Car(car) = &obj.details else {
+                continue;
+            };
+            let penalty = if car.is_wrecked() {
+                WRECK_PENALTY
+            } else {
+                CONGESTION_PENALTY
+            };
+            congestion.add(pixel_to_tile(car.position.to_coordinates()), penalty);
+        }
+
+        congestion
+    }
+}
+
+mod test {
+    use crate::gangsta::map::{Coordinates, Map};
+
+    use super::{Congestion, WRECK_PENALTY};
+
+    #[test]
+    fn test() {
+        let map = Map::new(50, 30);
+        let tile = |x, y| Coordinates { x, y };
+
+        // straight along the collector is shorter, but going round by the
+        // arterials and the interstate is cheaper
+        let path = map.find_path(tile(17, 6), tile(33, 6)).unwrap();
+        assert_eq!(path.first(), Some(&tile(17, 6)));
+        assert_eq!(path.last(), Some(&tile(33, 6)));
+        assert!(path.iter().any(|tile| tile.y == 0));
+
+        // the collector between the two local loops, unless it's blocked
+        let direct = map.find_path(tile(17, 20), tile(33, 20)).unwrap();
+        assert!(direct.iter().all(|tile| tile.y <= 20));
+        let mut congestion = Congestion::new(&map);
+        congestion.add(tile(25, 19), WRECK_PENALTY);
+        congestion.add(tile(26, 19), WRECK_PENALTY);
+        let around = map
+            .find_path_with(tile(17, 20), tile(33, 20), Some(&congestion))
+            .unwrap();
+        assert!(around.iter().any(|tile| tile.y == 24));
+
+        assert!(map.find_path(tile(17, 6), tile(10, 10)).is_none());
+
+        // on foot along the pavement beside the collector, not down the middle of it
+        assert!(map.is_sidewalk(tile(20, 18)) && !map.is_sidewalk(tile(20, 19)));
+        let walk = map.find_walk_path(tile(20, 18), tile(30, 18)).unwrap();
+        assert!(walk.iter().all(|tile| tile.y == 18));
+    }
+}
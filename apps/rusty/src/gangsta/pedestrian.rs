@@ -0,0 +1,541 @@
+use std::{
+    collections::{BTreeSet, VecDeque},
+    f32::consts::{FRAC_PI_2, TAU},
+    time::Duration,
+};
+
+use rand::Rng;
+
+use super::{
+    collision::{Body, Shape},
+    events::{DamageSource, GameEventKind, VIEW_DISTANCE},
+    map::{pixel_to_tile, tile_center, Coordinates},
+    police::Crime,
+    vector::{wrap_angle, Vector},
+    weapon::WeaponState,
+    GameObject, GameObjectInfo, GameObjectType, GameState, OutgoingGameObject, PersonDetails,
+    PersonSkin, MAX_HEALTH, PLAYER_MASS, PLAYER_RADIUS,
+};
+
+const DEFAULT_DENSITY: usize = 8;
+// pixels per second
+const WALK_SPEED: f32 = 40.0;
+const RUN_SPEED: f32 = 110.0;
+const WAYPOINT_RADIUS: f32 = 4.0;
+// people turn up just out of view, like the traffic does
+const SPAWN_MAX_DISTANCE: f32 = VIEW_DISTANCE + 160.0;
+const DESPAWN_DISTANCE: f32 = VIEW_DISTANCE + 320.0;
+const SPAWN_ATTEMPTS: u32 = 10;
+const SIDEWALK_SEARCH_RADIUS: i32 = 4;
+// strolls go to a pavement tile this many tiles away
+const WANDER_RADIUS: i32 = 12;
+// walks planned per tick, everyone else stands around until the next one
+const WANDERS_PER_TICK: usize = 4;
+// everyone within earshot of a shot or an explosion runs
+const ALARM_RADIUS: f32 = 320.0;
+// a car this fast coming at someone this close sends them running
+const DANGER_SPEED: f32 = 120.0;
+const DANGER_RANGE: f32 = 96.0;
+const FLEE_TIME: Duration = Duration::from_secs(4);
+const FLEE_DISTANCE: f32 = 160.0;
+// how long a body lies in the street
+const CORPSE_TIME: Duration = Duration::from_secs(10);
+
+// someone walking around town
+#[derive(Debug, Clone)]
+pub struct Pedestrian {
+    pub id: String,
+    pub position: Vector,
+    pub heading: f32,
+    pub velocity: Vector,
+    pub health: f32,
+    pub path: VecDeque<Vector>,
+    // running away until then
+    pub fleeing_until: Option<Duration>,
+    pub died_at: Option<Duration>,
+}
+
+impl Pedestrian {
+    pub fn new(id: String, position: Vector) -> Self {
+        Self {
+            id,
+            position,
+            heading: 0.0,
+            velocity: Vector::ZERO,
+            health: MAX_HEALTH,
+            path: VecDeque::new(),
+            fleeing_until: None,
+            died_at: None,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.health > 0.0
+    }
+
+    pub fn is_fleeing(&self) -> bool {
+        self.fleeing_until.is_some()
+    }
+
+    // the sprite faces up, like the players' do
+    pub fn rotation(&self) -> f32 {
+        wrap_angle(self.heading + FRAC_PI_2)
+    }
+
+    pub fn tick(&mut self, dt: Duration) {
+        self.velocity = Vector::ZERO;
+        if !self.is_alive() {
+            return;
+        }
+        while self
+            .path
+            .front()
+            .is_some_and(|waypoint| (*waypoint - self.position).length() < WAYPOINT_RADIUS)
+        {
+            self.path.pop_front();
+        }
+        let Some(target) = self.path.front().copied() else {
+            return;
+        };
+
+        let speed = if self.is_fleeing() {
+            RUN_SPEED
+        } else {
+            WALK_SPEED
+        };
+        let to_target = target - self.position;
+        let step = (speed * dt.as_secs_f32()).min(to_target.length());
+        let direction = to_target.normalized();
+        self.position += direction * step;
+        self.velocity = direction * speed;
+        self.heading = direction.y.atan2(direction.x);
+    }
+
+    // the dead are stepped over rather than bumped into
+    pub fn body(&self, id: &str) -> Option<Body> {
+        self.is_alive().then(|| Body {
+            id: id.to_string(),
+            position: self.position,
+            heading: self.heading,
+            velocity: self.velocity,
+            shape: Shape::Circle {
+                radius: PLAYER_RADIUS,
+            },
+            mass: PLAYER_MASS,
+        })
+    }
+
+    pub fn to_outgoing_game_object(&self) -> OutgoingGameObject {
+        OutgoingGameObject {
+            id: self.id.clone(),
+            x: self.position.x,
+            y: self.position.y,
+            rotation: self.rotation(),
+            velocity: self.velocity,
+            angular_velocity: 0.0,
+            owner_user_id: None,
+            controller_user_id: None,
+            details: GameObjectInfo::Person(PersonDetails {
+                user_id: self.id.clone(),
+                skin: PersonSkin::Default,
+                vehicle_id: None,
+                health: self.health,
+                alive: self.is_alive(),
+                weapon: WeaponState::default().kind,
+                wanted_level: 0,
+                pulling_out_of: None,
+                npc: true,
+                zone: None,
+            }),
+            action: None,
+        }
+    }
+}
+
+// ambient people, spawned and retired around the players like the traffic
+#[derive(Debug)]
+pub struct PedestrianManager {
+    // pedestrians kept around each player
+    pub density: usize,
+    pedestrians: BTreeSet<String>,
+    // the last one wander looked at, the next pass carries on after it
+    wander_cursor: Option<String>,
+}
+
+impl Default for PedestrianManager {
+    fn default() -> Self {
+        Self {
+            density: DEFAULT_DENSITY,
+            pedestrians: BTreeSet::new(),
+            wander_cursor: None,
+        }
+    }
+}
+
+impl PedestrianManager {
+    pub fn pedestrians(&self) -> impl Iterator<Item = &String> {
+        self.pedestrians.iter()
+    }
+}
+
+impl GameState {
+    pub fn pedestrian(&self, id: &str) -> Option<&Pedestrian> {
+        match &self.objects.get(id)?.details {
+            GameObjectType::Pedestrian(pedestrian) => Some(pedestrian),
+            _ => None,
+        }
+    }
+
+    fn pedestrian_mut(&mut self, id: &str) -> Option<&mut Pedestrian> {
+        match &mut self.objects.get_mut(id)?.details {
+            GameObjectType::Pedestrian(pedestrian) => Some(pedestrian),
+            _ => None,
+        }
+    }
+
+    pub(super) fn update_pedestrians(&mut self) {
+        let players: Vec<Vector> = self
+            .players
+            .values()
+            .filter(|player| player.is_alive())
+            .map(|player| player.position())
+            .collect();
+
+        self.retire_pedestrians(&players);
+        self.spot_danger();
+        self.wander();
+        for position in players {
+            self.fill_pedestrians(position);
+        }
+    }
+
+    // gunfire and explosions, everyone alive within earshot runs away from it
+    pub(super) fn raise_alarm(&mut self, from: Vector) {
+        let scared: Vec<String> = self
+            .pedestrians
+            .pedestrians
+            .iter()
+            .filter(|id| {
+                self.pedestrian(id).is_some_and(|pedestrian| {
+                    pedestrian.is_alive() && (pedestrian.position - from).length() <= ALARM_RADIUS
+                })
+            })
+            .cloned()
+            .collect();
+        for id in scared {
+            self.flee(&id, from);
+        }
+    }
+
+    pub fn damage_pedestrian(
+        &mut self,
+        id: &str,
+        amount: f32,
+        source: DamageSource,
+        attacker: Option<String>,
+    ) {
+        let time = self.time;
+        let Some(pedestrian) = self.pedestrian_mut(id) else {
+            return;
+        };
+        if amount <= 0.0 || !pedestrian.is_alive() {
+            return;
+        }
+
+        pedestrian.health = (pedestrian.health - amount).max(0.0);
+        let died = !pedestrian.is_alive();
+        if died {
+            pedestrian.died_at = Some(time);
+            pedestrian.path.clear();
+        }
+
+        self.events.push(
+            self.tick,
+            self.time,
+            GameEventKind::Damage {
+                target: id.to_string(),
+                amount,
+                source: source.clone(),
+                attacker: attacker.clone(),
+            },
+        );
+        if let Some(attacker) = attacker.as_deref() {
+            let crime = match source {
+                DamageSource::Vehicle { .. } => Crime::HitPedestrian,
+                _ => Crime::AttackedPlayer,
+            };
+            self.commit_crime(attacker, crime);
+        }
+        if died {
+            self.events.push(
+                self.tick,
+                self.time,
+                GameEventKind::Death {
+                    target: id.to_string(),
+                    source,
+                    killer: attacker,
+                },
+            );
+        }
+    }
+
+    // drops pedestrians that are gone, old bodies and anyone far from every player
+    fn retire_pedestrians(&mut self, players: &[Vector]) {
+        let ids: Vec<String> = self.pedestrians.pedestrians.iter().cloned().collect();
+        for id in ids {
+            let Some(pedestrian) = self.pedestrian(&id) else {
+                self.pedestrians.pedestrians.remove(&id);
+                continue;
+            };
+            let buried = pedestrian
+                .died_at
+                .is_some_and(|at| self.time >= at + CORPSE_TIME);
+            let near = players
+                .iter()
+                .any(|player| (*player - pedestrian.position).length() <= DESPAWN_DISTANCE);
+            if buried || !near {
+                self.pedestrians.pedestrians.remove(&id);
+                self.objects.remove(&id);
+            }
+        }
+    }
+
+    // runs from speeding cars, and calms down again once the danger has passed
+    fn spot_danger(&mut self) {
+        let cars: Vec<(Vector, Vector)> = self
+            .objects
+            .values()
+            .filter_map(|obj| match &obj.details {
+                GameObjectType::Car(car) if car.speed.abs() >= DANGER_SPEED => {
+                    Some((car.position, car.velocity))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut threats = vec![];
+        let mut calmed = vec![];
+        for id in self.pedestrians.pedestrians.iter() {
+            let Some(pedestrian) = self.pedestrian(id).filter(|p| p.is_alive()) else {
+                continue;
+            };
+            let threat = cars.iter().find(|(position, velocity)| {
+                let offset = pedestrian.position - *position;
+                offset.length() <= DANGER_RANGE && velocity.dot(offset) > 0.0
+            });
+            if let Some((position, _)) = threat {
+                threats.push((id.clone(), *position));
+            } else if pedestrian
+                .fleeing_until
+                .is_some_and(|until| self.time >= until)
+            {
+                calmed.push(id.clone());
+            }
+        }
+
+        for (id, from) in threats {
+            self.flee(&id, from);
+        }
+        for id in calmed {
+            if let Some(pedestrian) = self.pedestrian_mut(&id) {
+                pedestrian.fleeing_until = None;
+                pedestrian.path.clear();
+            }
+        }
+    }
+
+    fn flee(&mut self, id: &str, from: Vector) {
+        let angle = self.rng.gen_range(0.0..TAU);
+        let until = self.time + FLEE_TIME;
+        let Some(pedestrian) = self.pedestrian_mut(id) else {
+            return;
+        };
+        let mut away = (pedestrian.position - from).normalized();
+        if away == Vector::ZERO {
+            away = Vector::from_angle(angle);
+        }
+        // straight away from it, walls stop them like anyone else
+        pedestrian.path = VecDeque::from([pedestrian.position + away * FLEE_DISTANCE]);
+        pedestrian.fleeing_until = Some(until);
+    }
+
+    // people with nowhere to go stroll to somewhere nearby on the pavement.
+    // each pass picks up where the last one stopped, so someone who can't
+    // get anywhere doesn't hold up everyone behind them
+    fn wander(&mut self) {
+        let cursor = self.pedestrians.wander_cursor.as_deref();
+        let (after, before): (Vec<&String>, Vec<&String>) = self
+            .pedestrians
+            .pedestrians
+            .iter()
+            .partition(|id| cursor.is_some_and(|cursor| id.as_str() > cursor));
+        let idle: Vec<String> = after
+            .into_iter()
+            .chain(before)
+            .filter(|id| {
+                self.pedestrian(id).is_some_and(|pedestrian| {
+                    pedestrian.is_alive() && !pedestrian.is_fleeing() && pedestrian.path.is_empty()
+                })
+            })
+            .take(WANDERS_PER_TICK)
+            .cloned()
+            .collect();
+        if let Some(last) = idle.last() {
+            self.pedestrians.wander_cursor = Some(last.clone());
+        }
+
+        for id in idle {
+            let Some(position) = self.pedestrian(&id).map(|pedestrian| pedestrian.position) else {
+                continue;
+            };
+            let start = pixel_to_tile(position.to_coordinates());
+            let target = Coordinates {
+                x: start.x + self.rng.gen_range(-WANDER_RADIUS..=WANDER_RADIUS),
+                y: start.y + self.rng.gen_range(-WANDER_RADIUS..=WANDER_RADIUS),
+            };
+            let Some(goal) = self.map.nearest_sidewalk(target, SIDEWALK_SEARCH_RADIUS) else {
+                continue;
+            };
+            let Some(tiles) = self.map.find_walk_path(start, goal) else {
+                continue;
+            };
+            if let Some(pedestrian) = self.pedestrian_mut(&id) {
+                pedestrian.path = tiles.into_iter().skip(1).map(tile_center).collect();
+            }
+        }
+    }
+
+    // tops up the people walking around a player, at most one per tick
+    fn fill_pedestrians(&mut self, player: Vector) {
+        let nearby = self
+            .pedestrians
+            .pedestrians
+            .iter()
+            .filter_map(|id| self.pedestrian(id))
+            .filter(|pedestrian| (pedestrian.position - player).length() <= DESPAWN_DISTANCE)
+            .count();
+        if nearby >= self.pedestrians.density {
+            return;
+        }
+
+        for _ in 0..SPAWN_ATTEMPTS {
+            let angle = self.rng.gen_range(0.0..TAU);
+            let distance = self.rng.gen_range(VIEW_DISTANCE..SPAWN_MAX_DISTANCE);
+            let point = player + Vector::from_angle(angle) * distance;
+            let Some(tile) = self.map.nearest_sidewalk(
+                pixel_to_tile(point.to_coordinates()),
+                SIDEWALK_SEARCH_RADIUS,
+            ) else {
+                continue;
+            };
+            let position = tile_center(tile);
+            let seen = self
+                .players
+                .values()
+                .any(|player| (player.position() - position).length() < VIEW_DISTANCE);
+            if seen {
+                continue;
+            }
+
+            self.next_object_id += 1;
+            let id = format!("ped-{}", self.next_object_id);
+            self.objects.insert(
+                id.clone(),
+                GameObject {
+                    details: GameObjectType::Pedestrian(Pedestrian::new(id.clone(), position)),
+                },
+            );
+            self.pedestrians.pedestrians.insert(id);
+            return;
+        }
+    }
+}
+
+mod test {
+    use std::time::Duration;
+
+    use crate::gangsta::{
+        events::DamageSource,
+        map::{tile_center, Coordinates},
+        map_registry::MapRegistry,
+        vector::Vector,
+        GameState,
+    };
+
+    use super::{Pedestrian, FLEE_TIME, WANDERS_PER_TICK};
+
+    #[test]
+    fn test() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(5, map);
+        state.pedestrians.density = 2;
+        for _ in 0..20 {
+            state.step(Duration::from_millis(50));
+        }
+        let spawned: Vec<String> = state.pedestrians.pedestrians().cloned().collect();
+        assert!(!spawned.is_empty() && spawned.len() <= 2);
+        let id = spawned[0].clone();
+        let object = state.pedestrian(&id).unwrap().to_outgoing_game_object();
+        assert!(matches!(
+            object.details,
+            crate::gangsta::GameObjectInfo::Person(person) if person.npc
+        ));
+
+        // a shot nearby sends them running away from it
+        let position = state.players["tim"].position() + Vector::new(0.0, 100.0);
+        state.pedestrian_mut(&id).unwrap().position = position;
+        state.raise_alarm(position + Vector::new(40.0, 0.0));
+        let pedestrian = state.pedestrian(&id).unwrap();
+        assert!(pedestrian.is_fleeing());
+        assert!(pedestrian.path[0].x < position.x);
+        let steps = FLEE_TIME.as_millis() / 50 + 2;
+        for _ in 0..steps {
+            state.step(Duration::from_millis(50));
+        }
+        assert!(!state.pedestrian(&id).unwrap().is_fleeing());
+
+        // run over, the driver is on the hook for it
+        let source = DamageSource::Vehicle {
+            vehicle_id: "map-tim's car".to_string(),
+        };
+        state.damage_pedestrian(&id, 200.0, source, Some("tim".to_string()));
+        let pedestrian = state.pedestrian(&id).unwrap();
+        assert!(!pedestrian.is_alive());
+        assert!(pedestrian.body(&id).is_none());
+        assert!(state.players["tim"].wanted.level() > 0);
+    }
+
+    #[test]
+    fn wander() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(5, map);
+        let mut add = |id: &str, position: Vector| {
+            state.objects.insert(
+                id.to_string(),
+                crate::gangsta::GameObject {
+                    details: crate::gangsta::GameObjectType::Pedestrian(Pedestrian::new(
+                        id.to_string(),
+                        position,
+                    )),
+                },
+            );
+            state.pedestrians.pedestrians.insert(id.to_string());
+        };
+        // a full tick's worth stuck off the map, ahead of one who isn't
+        for index in 0..WANDERS_PER_TICK {
+            add(&format!("a-{index}"), Vector::new(-5000.0, -5000.0));
+        }
+        // next to the main road
+        let sidewalk = Coordinates { x: 40, y: 16 };
+        assert!(state.map.is_sidewalk(sidewalk));
+        add("b", tile_center(sidewalk));
+
+        state.wander();
+        assert!(state.pedestrian("b").unwrap().path.is_empty());
+        // b gets a turn now, it may take a few tries to pick somewhere reachable
+        for _ in 0..10 {
+            state.wander();
+        }
+        assert!(!state.pedestrian("b").unwrap().path.is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::{
+    collision,
+    events::GameEventKind,
+    map::{pixel_to_tile, tile_center},
+    path_planner::PathPurpose,
+    vector::Vector,
+    vehicle::{Vehicle, VehicleBehavior},
+    CarSkin, GameObject, GameObjectType, GameState,
+};
+
+pub const MAX_WANTED_LEVEL: u8 = 5;
+const HEAT_PER_LEVEL: f32 = 100.0;
+// once no unit has seen the suspect for this long, heat starts to drop
+const ESCAPE_TIME: Duration = Duration::from_secs(10);
+const HEAT_DECAY: f32 = 20.0;
+const SIGHT_RANGE: f32 = 320.0;
+// units turn up out of sight, somewhere in this ring around the suspect
+const SPAWN_MIN_DISTANCE: f32 = 240.0;
+const SPAWN_MAX_DISTANCE: f32 = 400.0;
+const SPAWN_ATTEMPTS: u32 = 20;
+const REPLAN_INTERVAL: Duration = Duration::from_secs(1);
+// close enough to leave the road and drive straight at the suspect
+const CLOSE_PURSUIT: f32 = 64.0;
+const ROAD_SEARCH_RADIUS: i32 = 8;
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Crime {
+    HitPedestrian,
+    StoleVehicle,
+    AttackedPlayer,
+    AttackedPolice,
+}
+
+impl Crime {
+    fn heat(&self) -> f32 {
+        match self {
+            Crime::HitPedestrian => 60.0,
+            Crime::StoleVehicle => 80.0,
+            Crime::AttackedPlayer => 40.0,
+            Crime::AttackedPolice => 100.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Wanted {
+    level: u8,
+    heat: f32,
+    last_seen: Duration,
+}
+
+impl Wanted {
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    // each of these returns whether the level changed
+    fn add(&mut self, crime: Crime, now: Duration) -> bool {
+        self.heat = (self.heat + crime.heat()).min(HEAT_PER_LEVEL * MAX_WANTED_LEVEL as f32);
+        self.last_seen = now;
+        self.update_level()
+    }
+
+    fn decay(&mut self, now: Duration, dt: Duration) -> bool {
+        if self.heat <= 0.0 || now < self.last_seen + ESCAPE_TIME {
+            return false;
+        }
+        self.heat = (self.heat - HEAT_DECAY * dt.as_secs_f32()).max(0.0);
+        self.update_level()
+    }
+
+    fn clear(&mut self) -> bool {
+        self.heat = 0.0;
+        self.update_level()
+    }
+
+    fn update_level(&mut self) -> bool {
+        let level = ((self.heat / HEAT_PER_LEVEL).ceil() as u8).min(MAX_WANTED_LEVEL);
+        let changed = level != self.level;
+        self.level = level;
+        changed
+    }
+}
+
+// one more unit per level, and they drive harder the higher it gets
+fn units_for(level: u8) -> usize {
+    level as usize
+}
+
+fn behavior_for(level: u8) -> VehicleBehavior {
+    match level {
+        0 | 1 => VehicleBehavior::Cautious,
+        2 => VehicleBehavior::Normal,
+        _ => VehicleBehavior::Aggressive,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PoliceUnit {
+    pub suspect: String,
+    replan_at: Duration,
+}
+
+impl GameState {
+    pub fn commit_crime(&mut self, user_id: &str, crime: Crime) {
+        let now = self.time;
+        let Some(player) = self.players.get_mut(user_id) else {
+            return;
+        };
+        if player.wanted.add(crime, now) {
+            let level = player.wanted.level();
+            self.wanted_level_changed(user_id, level);
+        }
+    }
+
+    pub fn is_police_unit(&self, vehicle_id: &str) -> bool {
+        self.police.contains_key(vehicle_id)
+    }
+
+    // a stolen unit stops chasing anyone
+    pub(super) fn release_police_unit(&mut self, vehicle_id: &str) {
+        self.police.remove(vehicle_id);
+    }
+
+    fn wanted_level_changed(&mut self, user_id: &str, level: u8) {
+        self.events.push(
+            self.tick,
+            self.time,
+            GameEventKind::WantedLevel {
+                target: user_id.to_string(),
+                level,
+            },
+        );
+    }
+
+    pub(super) fn update_police(&mut self, dt: Duration) {
+        self.update_wanted_levels(dt);
+
+        // units whose suspect got away, died or left, and units that got wrecked
+        let finished: Vec<String> = self
+            .police
+            .iter()
+            .filter(|(id, unit)| {
+                let wanted = self
+                    .players
+                    .get(&unit.suspect)
+                    .is_some_and(|player| player.wanted.level() > 0);
+                let running = self.vehicle(id).is_some_and(|car| !car.is_wrecked());
+                !(wanted && running)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        // the ones still running join the traffic and drive off, rather than
+        // vanishing in front of whoever is watching
+        for id in finished {
+            self.police.remove(&id);
+            self.paths.cancel(&id);
+            if let Some(car) = self.vehicle_mut(&id).filter(|car| !car.is_wrecked()) {
+                car.set_path(vec![]);
+                self.traffic.adopt(id);
+            }
+        }
+
+        let suspects: Vec<(String, u8)> = self
+            .players
+            .values()
+            .filter(|player| player.wanted.level() > 0)
+            .map(|player| (player.id.clone(), player.wanted.level()))
+            .collect();
+        for (suspect, level) in suspects {
+            let units = self
+                .police
+                .values()
+                .filter(|unit| unit.suspect == suspect)
+                .count();
+            for _ in units..units_for(level) {
+                if !self.spawn_police_unit(&suspect) {
+                    break;
+                }
+            }
+        }
+
+        let due: Vec<String> = self
+            .police
+            .iter()
+            .filter(|(_, unit)| self.time >= unit.replan_at)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in due {
+            self.plan_pursuit(&id);
+        }
+    }
+
+    fn update_wanted_levels(&mut self, dt: Duration) {
+        let units: Vec<Vector> = self
+            .police
+            .keys()
+            .filter_map(|id| self.vehicle(id))
+            .filter(|car| !car.is_wrecked())
+            .map(|car| car.position)
+            .collect();
+
+        let mut changed = vec![];
+        for player in self.players.values_mut() {
+            if player.wanted.level() == 0 {
+                continue;
+            }
+            if !player.is_alive() {
+                // dying settles the score
+                if player.wanted.clear() {
+                    changed.push((player.id.clone(), 0));
+                }
+                continue;
+            }
+
+            let position = player.position();
+            let seen = units.iter().any(|unit| can_see(&self.map, *unit, position));
+            if seen {
+                player.wanted.last_seen = self.time;
+            }
+            if player.wanted.decay(self.time, dt) {
+                changed.push((player.id.clone(), player.wanted.level()));
+            }
+        }
+
+        for (user_id, level) in changed {
+            self.wanted_level_changed(&user_id, level);
+        }
+    }
+
+    // picks a road tile near a random point in a ring around the suspect,
+    // returns false if none turned up
+    fn spawn_police_unit(&mut self, suspect: &str) -> bool {
+        let Some(target) = self.players.get(suspect).map(|player| player.position()) else {
+            return false;
+        };
+
+        for _ in 0..SPAWN_ATTEMPTS {
+            let angle = self.rng.gen_range(0.0..TAU);
+            let distance = self.rng.gen_range(SPAWN_MIN_DISTANCE..SPAWN_MAX_DISTANCE);
+            let point = target + Vector::from_angle(angle) * distance;
+            let Some(tile) = self
+                .map
+                .nearest_road(pixel_to_tile(point.to_coordinates()), ROAD_SEARCH_RADIUS)
+            else {
+                continue;
+            };
+            // snapping to the road may have pulled it right next to the suspect
+            if (tile_center(tile) - target).length() < SPAWN_MIN_DISTANCE {
+                continue;
+            }
+
+            self.next_object_id += 1;
+            let id = format!("police-{}", self.next_object_id);
+            let position = tile_center(tile);
+            let mut car = Vehicle::new(
+                id.clone(),
+                position,
+                VehicleBehavior::Cautious,
+                CarSkin::Police,
+            );
+            let to_target = target - position;
+            car.heading = to_target.y.atan2(to_target.x);
+
+            self.objects.insert(
+                id.clone(),
+                GameObject {
+                    details: GameObjectType::Car(car),
+                },
+            );
+            self.police.insert(
+                id.clone(),
+                PoliceUnit {
+                    suspect: suspect.to_string(),
+                    replan_at: self.time,
+                },
+            );
+            return true;
+        }
+
+        false
+    }
+
+    // follows the roads towards the suspect and then goes straight for them,
+    // re-planned every REPLAN_INTERVAL as both of them move
+    fn plan_pursuit(&mut self, unit_id: &str) {
+        let Some(unit) = self.police.get_mut(unit_id) else {
+            return;
+        };
+        unit.replan_at = self.time + REPLAN_INTERVAL;
+        let suspect = unit.suspect.clone();
+
+        let (Some(car), Some(player)) = (self.vehicle(unit_id), self.players.get(&suspect)) else {
+            return;
+        };
+        let target = player.position();
+        let level = player.wanted.level();
+
+        // close enough to go straight for them, otherwise along the roads
+        let mut route = None;
+        if (target - car.position).length() > CLOSE_PURSUIT {
+            let start = self.map.nearest_road(
+                pixel_to_tile(car.position.to_coordinates()),
+                ROAD_SEARCH_RADIUS,
+            );
+            let goal = self
+                .map
+                .nearest_road(pixel_to_tile(target.to_coordinates()), ROAD_SEARCH_RADIUS);
+            route = start.zip(goal);
+        }
+
+        let Some(car) = self.vehicle_mut(unit_id) else {
+            return;
+        };
+        if car.driver_user_id.is_some() {
+            return;
+        }
+        car.behavior = behavior_for(level);
+        match route {
+            Some((start, goal)) => {
+                self.request_path(unit_id, start, goal, PathPurpose::Pursuit { target })
+            }
+            None => {
+                car.set_path(vec![target]);
+            }
+        }
+    }
+}
+
+fn can_see(map: &super::map::Map, from: Vector, to: Vector) -> bool {
+    let offset = to - from;
+    let distance = offset.length();
+    distance <= SIGHT_RANGE
+        && collision::ray_blocked(map, from, offset.normalized(), distance).is_none()
+}
+
+mod test {
+    use std::time::Duration;
+
+    use super::{Crime, Wanted, ESCAPE_TIME};
+
+    #[test]
+    fn test() {
+        let mut wanted = Wanted::default();
+        assert!(wanted.add(Crime::AttackedPlayer, Duration::ZERO));
+        assert_eq!(wanted.level(), 1);
+        assert!(!wanted.add(Crime::AttackedPlayer, Duration::ZERO));
+        assert!(wanted.add(Crime::StoleVehicle, Duration::ZERO));
+        assert_eq!(wanted.level(), 2);
+
+        // nothing drops while the police can still see you
+        assert!(!wanted.decay(ESCAPE_TIME / 2, Duration::from_secs(1)));
+        assert_eq!(wanted.level(), 2);
+
+        let mut now = ESCAPE_TIME;
+        while wanted.level() > 0 {
+            now += Duration::from_secs(1);
+            wanted.decay(now, Duration::from_secs(1));
+        }
+        // 160 heat at 20 a second
+        assert_eq!(now, ESCAPE_TIME + Duration::from_secs(8));
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    error::Error,
+    fs::{read_to_string, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::input_log::{InputLog, TickInputs};
+
+pub const REPLAY_VERSION: u32 = 1;
+const FLUSH_EVERY_TICKS: u32 = 20;
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    Paused,
+    Normal,
+    Double,
+}
+
+impl ReplaySpeed {
+    pub fn multiplier(&self) -> u32 {
+        match self {
+            ReplaySpeed::Paused => 0,
+            ReplaySpeed::Normal => 1,
+            ReplaySpeed::Double => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayHeader {
+    pub version: u32,
+    pub map: String,
+    pub seed: u64,
+    pub timestep: Duration,
+    pub players: Vec<String>,
+}
+
+// replay files are json lines: the header, then one TickInputs per simulated tick
+pub struct ReplayRecorder {
+    writer: BufWriter<File>,
+}
+
+impl ReplayRecorder {
+    pub fn create(path: &Path, header: &ReplayHeader) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, header)?;
+        writer.write_all(b"\n")?;
+
+        Ok(Self { writer })
+    }
+
+    pub fn write_tick(&mut self, tick: &TickInputs) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, tick)?;
+        self.writer.write_all(b"\n")?;
+        if tick.tick.is_multiple_of(FLUSH_EVERY_TICKS) {
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub log: InputLog,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Replay, Box<dyn Error>> {
+        let contents = read_to_string(path)?;
+        let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+
+        let header: ReplayHeader = serde_json::from_str(lines.next().ok_or("Empty replay file")?)?;
+        if header.version != REPLAY_VERSION {
+            return Err(format!("Unsupported replay version {}", header.version).into());
+        }
+
+        let mut log = InputLog::new(header.seed);
+        log.timestep = header.timestep;
+        for line in lines {
+            let tick: TickInputs = match serde_json::from_str(line) {
+                Ok(tick) => tick,
+                // the last line of a replay that is still being written may be cut off
+                Err(_) => break,
+            };
+            log.total_ticks = tick.tick;
+            if !tick.inputs.is_empty() {
+                log.ticks.push(tick);
+            }
+        }
+
+        Ok(Replay { header, log })
+    }
+}
+
+pub fn replay_dir() -> Option<PathBuf> {
+    dotenv::var("REPLAY_DIR").ok().map(PathBuf::from)
+}
+
+pub fn replay_path(dir: &Path, replay_id: &str) -> Option<PathBuf> {
+    let valid = !replay_id.is_empty()
+        && replay_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    valid.then(|| dir.join(format!("{}.replay", replay_id)))
+}
+
+mod test {
+    use std::time::Duration;
+
+    use crate::gangsta::{input_log::GameCommand, map_registry::MapRegistry, GameState};
+
+    use super::Replay;
+
+    #[test]
+    fn test() {
+        let path = std::env::temp_dir().join(format!("{}.replay", ulid::Ulid::new()));
+        let timestep = Duration::from_millis(50);
+
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let play = |state: &mut GameState| {
+            for tick in 0..45 {
+                if tick % 4 == 0 {
+                    state.queue_input(
+                        "bob".to_string(),
+                        GameCommand::Move {
+                            x: 700 - tick,
+                            y: 640 + tick,
+                            rotation: 1.5,
+                        },
+                    );
+                }
+                state.step(timestep);
+            }
+        };
+
+        let mut state = GameState::new(7, map.clone());
+        state.start_recording(&path, timestep).unwrap();
+        play(&mut state);
+        drop(state.recorder.take());
+        // the inputs went to the file rather than piling up in memory
+        assert!(state.input_log.ticks.is_empty());
+        assert_eq!(state.input_log.total_ticks, 45);
+
+        let replay = Replay::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(replay.header.seed, 7);
+        assert_eq!(replay.header.players, vec!["bob", "tim"]);
+        let mut unrecorded = GameState::new(7, map);
+        play(&mut unrecorded);
+        assert_eq!(replay.log, unrecorded.input_log);
+
+        let replayed = GameState::replay(&replay.log, state.map.clone());
+        assert_eq!(
+            replayed.history.latest().unwrap().entities,
+            state.history.latest().unwrap().entities
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use super::map::{Coordinates, Map, RoadType, TileType};
+
+// roads are never wider than this, so a tile with road running further than
+// this in both directions sits where two roads meet
+const MAX_ROAD_WIDTH: usize = 10;
+// roads narrower than this are too tight to split into lanes
+const MIN_TWO_LANE_WIDTH: usize = 2;
+// moving sideways out of a lane is allowed, but only when it's worth it
+const LANE_CHANGE_COST: i32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::East => (1, 0),
+            Direction::South => (0, 1),
+            Direction::West => (-1, 0),
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+}
+
+// which way traffic may move through a road tile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lane {
+    OffRoad,
+    // junctions, bends and narrow roads, anything goes
+    Any,
+    OneWay(Direction),
+}
+
+impl Lane {
+    // None when the move goes against the lane, otherwise what it costs
+    fn cost(&self, direction: Direction) -> Option<i32> {
+        match self {
+            Lane::OffRoad => None,
+            Lane::Any => Some(1),
+            Lane::OneWay(lane) if *lane == direction => Some(1),
+            Lane::OneWay(lane) if lane.opposite() == direction => None,
+            Lane::OneWay(_) => Some(LANE_CHANGE_COST),
+        }
+    }
+}
+
+// the straight stretch of road a tile is part of along one axis
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Run {
+    pub start: usize,
+    pub length: usize,
+}
+
+// runs of road through every tile, horizontally and vertically
+pub struct RoadRuns {
+    pub horizontal: Vec<Vec<Run>>,
+    pub vertical: Vec<Vec<Run>>,
+}
+
+impl RoadRuns {
+    pub fn new(map: &Map) -> Self {
+        let (width, height) = (map.width(), map.height());
+        let is_road = |x: usize, y: usize| matches!(map.grid[y][x].tile_type, TileType::Road(_));
+
+        let mut horizontal = vec![vec![Run::default(); width]; height];
+        let mut vertical = vec![vec![Run::default(); width]; height];
+        for (y, row) in horizontal.iter_mut().enumerate() {
+            let mut x = 0;
+            while x < width {
+                let start = x;
+                while x < width && is_road(x, y) {
+                    x += 1;
+                }
+                for run in row.iter_mut().skip(start).take(x - start) {
+                    *run = Run {
+                        start,
+                        length: x - start,
+                    };
+                }
+                x += 1;
+            }
+        }
+        for x in 0..width {
+            let mut y = 0;
+            while y < height {
+                let start = y;
+                while y < height && is_road(x, y) {
+                    y += 1;
+                }
+                for row in vertical.iter_mut().skip(start).take(y - start) {
+                    row[x] = Run {
+                        start,
+                        length: y - start,
+                    };
+                }
+                y += 1;
+            }
+        }
+
+        Self {
+            horizontal,
+            vertical,
+        }
+    }
+
+    pub fn is_junction(&self, x: usize, y: usize) -> bool {
+        self.horizontal[y][x].length > MAX_ROAD_WIDTH && self.vertical[y][x].length > MAX_ROAD_WIDTH
+    }
+
+    // traffic keeps right: on a road running east-west the southern half
+    // drives east, on one running north-south the eastern half drives north
+    fn lane(&self, x: usize, y: usize) -> Lane {
+        let (horizontal, vertical) = (self.horizontal[y][x], self.vertical[y][x]);
+        if horizontal.length == 0 {
+            return Lane::OffRoad;
+        }
+
+        let along_x = horizontal.length > MAX_ROAD_WIDTH && vertical.length <= MAX_ROAD_WIDTH;
+        let along_y = vertical.length > MAX_ROAD_WIDTH && horizontal.length <= MAX_ROAD_WIDTH;
+        if along_x && vertical.length >= MIN_TWO_LANE_WIDTH {
+            if (y - vertical.start) * 2 < vertical.length {
+                Lane::OneWay(Direction::West)
+            } else {
+                Lane::OneWay(Direction::East)
+            }
+        } else if along_y && horizontal.length >= MIN_TWO_LANE_WIDTH {
+            if (x - horizontal.start) * 2 < horizontal.length {
+                Lane::OneWay(Direction::South)
+            } else {
+                Lane::OneWay(Direction::North)
+            }
+        } else {
+            Lane::Any
+        }
+    }
+}
+
+// the road tiles and the moves allowed between them, stored row by row
+#[derive(Debug, Clone, Default)]
+pub struct RoadGraph {
+    width: usize,
+    height: usize,
+    lanes: Vec<Lane>,
+    // RoadType::cost of every tile, 0 off the road
+    costs: Vec<i32>,
+}
+
+impl RoadGraph {
+    pub fn new(map: &Map) -> Self {
+        let runs = RoadRuns::new(map);
+        let (width, height) = (map.width(), map.height());
+        let mut lanes = Vec::with_capacity(width * height);
+        let mut costs = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                lanes.push(runs.lane(x, y));
+                costs.push(match map.grid[y][x].tile_type {
+                    TileType::Road(road_type) => road_type.cost(),
+                    _ => 0,
+                });
+            }
+        }
+
+        Self {
+            width,
+            height,
+            lanes,
+            costs,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // where the tile lives in the flat per-tile arrays
+    pub fn index(&self, tile: Coordinates) -> Option<usize> {
+        if tile.x < 0
+            || tile.y < 0
+            || tile.x as usize >= self.width
+            || tile.y as usize >= self.height
+        {
+            return None;
+        }
+        Some(tile.y as usize * self.width + tile.x as usize)
+    }
+
+    pub fn tile(&self, index: usize) -> Coordinates {
+        Coordinates {
+            x: (index % self.width) as i32,
+            y: (index / self.width) as i32,
+        }
+    }
+
+    pub fn lane(&self, tile: Coordinates) -> Lane {
+        match self.index(tile) {
+            Some(index) => self.lanes[index],
+            None => Lane::OffRoad,
+        }
+    }
+
+    // the tiles reachable in one move and what each move costs, a move has
+    // to suit the lane it leaves and the one it enters and costs more on
+    // smaller roads. turning around is only possible at junctions
+    pub fn neighbours(&self, tile: Coordinates) -> impl Iterator<Item = (Coordinates, i32)> + '_ {
+        let from = self.lane(tile);
+        Direction::ALL.into_iter().filter_map(move |direction| {
+            let (dx, dy) = direction.offset();
+            let next = Coordinates {
+                x: tile.x + dx,
+                y: tile.y + dy,
+            };
+            let to = self.lane(next);
+            // crossing over to the other side only happens inside a junction
+            if let (Lane::OneWay(leaving), Lane::OneWay(entering)) = (from, to) {
+                if leaving.opposite() == entering {
+                    return None;
+                }
+            }
+            let leave = from.cost(direction)?;
+            let enter = to.cost(direction)?;
+            let road = self.costs[self.index(next)?];
+            Some((next, leave.max(enter) * road))
+        })
+    }
+
+    // no move costs less than this, which keeps the A* heuristic admissible
+    pub fn min_cost() -> i32 {
+        RoadType::Interstate.cost()
+    }
+}
+
+mod test {
+    use crate::gangsta::{map::Coordinates, map_registry::MapRegistry};
+
+    use super::{Direction, Lane};
+
+    #[test]
+    fn test() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let tile = |x, y| Coordinates { x, y };
+
+        // the main road runs east-west over rows 17 to 25
+        assert_eq!(
+            map.roads().lane(tile(40, 18)),
+            Lane::OneWay(Direction::West)
+        );
+        assert_eq!(
+            map.roads().lane(tile(40, 24)),
+            Lane::OneWay(Direction::East)
+        );
+        assert_eq!(map.roads().lane(tile(27, 20)), Lane::Any);
+        assert_eq!(map.roads().lane(tile(40, 5)), Lane::OffRoad);
+
+        let east = map.find_path(tile(10, 23), tile(60, 23)).unwrap();
+        assert!(east.iter().all(|tile| tile.y >= 22));
+
+        // heading back west means turning around at a junction and coming
+        // back along the northern half
+        let west = map.find_path(tile(60, 23), tile(10, 20)).unwrap();
+        let middle = west.iter().find(|tile| tile.x == 40).unwrap();
+        assert!(middle.y <= 21);
+        let crossing = west
+            .windows(2)
+            .find(|pair| pair[0].y >= 22 && pair[1].y <= 21)
+            .unwrap();
+        assert_eq!(map.roads().lane(crossing[0]), Lane::Any);
+        assert_eq!(map.roads().lane(crossing[1]), Lane::Any);
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use crate::error::{AppError, AppResult};
+
+use super::{
+    events::GameEventKind, police::Crime, vector::Vector, Controllable, GameState,
+    INTERACTION_RANGE,
+};
+
+// the thief has to stand still while dragging the driver out
+const THEFT_MOVE_TOLERANCE: f32 = 4.0;
+
+// a player busy pulling an NPC out of the driver seat
+#[derive(Debug, Clone)]
+pub struct PullOut {
+    pub vehicle_id: String,
+    complete_at: Duration,
+    start: Vector,
+}
+
+impl GameState {
+    pub(super) fn start_pull_out(&mut self, user_id: &str, vehicle_id: &str) -> AppResult<()> {
+        let duration = self
+            .vehicle(vehicle_id)
+            .ok_or(AppError::BadRequest("No such vehicle".to_string()))?
+            .params
+            .pull_out_time;
+        let complete_at = self.time + Duration::from_secs_f32(duration);
+        let player = self
+            .players
+            .get_mut(user_id)
+            .ok_or(AppError::BadRequest("Not in this game".to_string()))?;
+        player.pull_out = Some(PullOut {
+            vehicle_id: vehicle_id.to_string(),
+            complete_at,
+            start: player.position(),
+        });
+
+        self.events.push(
+            self.tick,
+            self.time,
+            GameEventKind::PullOutStarted {
+                thief: user_id.to_string(),
+                vehicle_id: vehicle_id.to_string(),
+                duration,
+            },
+        );
+        Ok(())
+    }
+
+    pub(super) fn interrupt_pull_out(&mut self, user_id: &str) {
+        let Some(pull_out) = self
+            .players
+            .get_mut(user_id)
+            .and_then(|player| player.pull_out.take())
+        else {
+            return;
+        };
+
+        self.events.push(
+            self.tick,
+            self.time,
+            GameEventKind::PullOutInterrupted {
+                thief: user_id.to_string(),
+                vehicle_id: pull_out.vehicle_id,
+            },
+        );
+    }
+
+    // finishes pull-outs that ran their course and drops the ones where the
+    // thief moved, died or the car got away
+    pub(super) fn update_pull_outs(&mut self) {
+        let pull_outs: Vec<(String, PullOut)> = self
+            .players
+            .values()
+            .filter_map(|player| Some((player.id.clone(), player.pull_out.clone()?)))
+            .collect();
+
+        for (user_id, pull_out) in pull_outs {
+            let player = &self.players[&user_id];
+            let car = self
+                .vehicle(&pull_out.vehicle_id)
+                .filter(|car| !car.is_wrecked() && car.npc_driver && car.driver_user_id.is_none());
+            let interrupted = match car {
+                Some(car) => {
+                    !player.is_alive()
+                        || player.vehicle_id.is_some()
+                        || (player.position() - pull_out.start).length() > THEFT_MOVE_TOLERANCE
+                        || (player.position() - car.position).length() > INTERACTION_RANGE
+                }
+                None => true,
+            };
+
+            if interrupted {
+                self.interrupt_pull_out(&user_id);
+            } else if self.time >= pull_out.complete_at {
+                self.complete_pull_out(&user_id, &pull_out.vehicle_id);
+            }
+        }
+    }
+
+    // the NPC driver is thrown out and the thief takes the wheel
+    fn complete_pull_out(&mut self, user_id: &str, vehicle_id: &str) {
+        if let Some(player) = self.players.get_mut(user_id) {
+            player.pull_out = None;
+        }
+        let Some(car) = self.vehicle_mut(vehicle_id) else {
+            return;
+        };
+        car.npc_driver = false;
+        car.take_control(user_id);
+        let (position, rotation) = (car.position, car.rotation());
+
+        if let Some(player) = self.players.get_mut(user_id) {
+            player.vehicle_id = Some(vehicle_id.to_string());
+            player.set_position(position);
+            player.rotation = rotation;
+        }
+        self.vehicle_stolen(user_id, vehicle_id);
+    }
+
+    // taking the wheel of a car somebody else owns
+    pub(super) fn vehicle_stolen(&mut self, user_id: &str, vehicle_id: &str) {
+        let Some(owner) = self.vehicle(vehicle_id).map(|car| car.owner.clone()) else {
+            return;
+        };
+
+        if let Some(car) = self.vehicle_mut(vehicle_id) {
+            car.stolen_by = Some(user_id.to_string());
+        }
+        self.release_police_unit(vehicle_id);
+        self.events.push(
+            self.tick,
+            self.time,
+            GameEventKind::VehicleStolen {
+                thief: user_id.to_string(),
+                vehicle_id: vehicle_id.to_string(),
+                owner,
+            },
+        );
+        self.commit_crime(user_id, Crime::StoleVehicle);
+    }
+}
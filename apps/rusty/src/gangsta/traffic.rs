@@ -0,0 +1,246 @@
+use std::{collections::BTreeSet, f32::consts::TAU};
+
+use rand::Rng;
+
+use super::{
+    events::VIEW_DISTANCE,
+    map::{pixel_to_tile, tile_center, Coordinates},
+    path_planner::PathPurpose,
+    road_graph::Lane,
+    vector::Vector,
+    vehicle::{Vehicle, VehicleBehavior},
+    CarSkin, GameObject, GameObjectType, GameState,
+};
+
+const DEFAULT_DENSITY: usize = 6;
+// cars turn up just past the edge of everyone's view
+const SPAWN_MAX_DISTANCE: f32 = VIEW_DISTANCE + 240.0;
+// and are retired once nobody is anywhere near them
+const DESPAWN_DISTANCE: f32 = VIEW_DISTANCE + 400.0;
+const SPAWN_ATTEMPTS: u32 = 10;
+// keeps new cars from appearing on top of another one
+const SPAWN_CLEARANCE: f32 = 32.0;
+// destinations are picked among road tiles this many tiles from the car
+const DESTINATION_RADIUS: i32 = 48;
+const DESTINATION_ATTEMPTS: u32 = 5;
+const ROAD_SEARCH_RADIUS: i32 = 4;
+
+// ambient AI cars, spawned and retired around the players
+#[derive(Debug)]
+pub struct TrafficManager {
+    // AI cars kept around each player
+    pub density: usize,
+    vehicles: BTreeSet<String>,
+}
+
+impl Default for TrafficManager {
+    fn default() -> Self {
+        Self {
+            density: DEFAULT_DENSITY,
+            vehicles: BTreeSet::new(),
+        }
+    }
+}
+
+impl TrafficManager {
+    pub fn vehicles(&self) -> impl Iterator<Item = &String> {
+        self.vehicles.iter()
+    }
+
+    // hands a car that was spawned elsewhere over to be routed and retired
+    pub fn adopt(&mut self, vehicle_id: String) {
+        self.vehicles.insert(vehicle_id);
+    }
+}
+
+impl GameState {
+    pub(super) fn update_traffic(&mut self) {
+        let players: Vec<Vector> = self
+            .players
+            .values()
+            .filter(|player| player.is_alive())
+            .map(|player| player.position())
+            .collect();
+
+        self.retire_traffic(&players);
+        self.route_traffic();
+        for position in players {
+            self.fill_traffic(position);
+        }
+    }
+
+    // drops cars that are gone and despawns empty ones far from every player
+    fn retire_traffic(&mut self, players: &[Vector]) {
+        let ids: Vec<String> = self.traffic.vehicles.iter().cloned().collect();
+        for id in ids {
+            let Some(car) = self.vehicle(&id) else {
+                self.traffic.vehicles.remove(&id);
+                continue;
+            };
+            let occupied = car.driver_user_id.is_some() || !car.passenger_user_ids.is_empty();
+            let near = players
+                .iter()
+                .any(|player| (*player - car.position).length() <= DESPAWN_DISTANCE);
+            if !occupied && !near {
+                self.traffic.vehicles.remove(&id);
+                self.destroy_vehicle(&id);
+            }
+        }
+    }
+
+    // cars that reached their destination head off somewhere new
+    fn route_traffic(&mut self) {
+        let arrived: Vec<String> = self
+            .traffic
+            .vehicles
+            .iter()
+            .filter(|id| {
+                self.vehicle(id).is_some_and(|car| {
+                    car.npc_driver
+                        && car.driver_user_id.is_none()
+                        && !car.is_wrecked()
+                        && car.path.is_empty()
+                })
+            })
+            .filter(|id| !self.paths.is_pending(id))
+            .cloned()
+            .collect();
+
+        for id in arrived {
+            let Some(position) = self.vehicle(&id).map(|car| car.position) else {
+                continue;
+            };
+            let start = pixel_to_tile(position.to_coordinates());
+            if let Some(goal) = self.random_destination(start) {
+                self.request_path(&id, start, goal, PathPurpose::Traffic);
+            }
+        }
+    }
+
+    // tops up the AI cars driving around a player, at most one per tick
+    fn fill_traffic(&mut self, player: Vector) {
+        let nearby = self
+            .traffic
+            .vehicles
+            .iter()
+            .filter_map(|id| self.vehicle(id))
+            .filter(|car| car.npc_driver && (car.position - player).length() <= DESPAWN_DISTANCE)
+            .count();
+        if nearby >= self.traffic.density {
+            return;
+        }
+
+        for _ in 0..SPAWN_ATTEMPTS {
+            let angle = self.rng.gen_range(0.0..TAU);
+            let distance = self.rng.gen_range(VIEW_DISTANCE..SPAWN_MAX_DISTANCE);
+            let point = player + Vector::from_angle(angle) * distance;
+            let Some(tile) = self
+                .map
+                .nearest_road(pixel_to_tile(point.to_coordinates()), ROAD_SEARCH_RADIUS)
+            else {
+                continue;
+            };
+            let position = tile_center(tile);
+            if !self.spawn_is_clear(position) {
+                continue;
+            }
+            let Some(goal) = self.random_destination(tile) else {
+                continue;
+            };
+
+            self.spawn_traffic(tile, goal);
+            return;
+        }
+    }
+
+    // out of everyone's view and not inside another car
+    fn spawn_is_clear(&self, position: Vector) -> bool {
+        let seen = self
+            .players
+            .values()
+            .any(|player| (player.position() - position).length() < VIEW_DISTANCE);
+        let blocked = self.objects.iter().any(|(id, obj)| {
+            obj.body(id)
+                .is_some_and(|body| (body.position - position).length() < SPAWN_CLEARANCE)
+        });
+
+        !seen && !blocked
+    }
+
+    // the route is planned in the background, until then the car waits in its lane
+    fn spawn_traffic(&mut self, tile: Coordinates, goal: Coordinates) {
+        self.next_object_id += 1;
+        let id = format!("traffic-{}", self.next_object_id);
+        let behavior = match self.rng.gen_range(0..4) {
+            0 => VehicleBehavior::Cautious,
+            1 => VehicleBehavior::Aggressive,
+            _ => VehicleBehavior::Normal,
+        };
+
+        let mut car = Vehicle::new(id.clone(), tile_center(tile), behavior, CarSkin::Sedan);
+        if let Lane::OneWay(direction) = self.map.roads().lane(tile) {
+            let (dx, dy) = direction.offset();
+            car.heading = (dy as f32).atan2(dx as f32);
+        }
+
+        self.objects.insert(
+            id.clone(),
+            GameObject {
+                details: GameObjectType::Car(car),
+            },
+        );
+        self.traffic.vehicles.insert(id.clone());
+        self.request_path(&id, tile, goal, PathPurpose::Traffic);
+    }
+
+    // a random road tile within DESTINATION_RADIUS
+    fn random_destination(&mut self, start: Coordinates) -> Option<Coordinates> {
+        for _ in 0..DESTINATION_ATTEMPTS {
+            let target = Coordinates {
+                x: start.x + self.rng.gen_range(-DESTINATION_RADIUS..=DESTINATION_RADIUS),
+                y: start.y + self.rng.gen_range(-DESTINATION_RADIUS..=DESTINATION_RADIUS),
+            };
+            match self.map.nearest_road(target, ROAD_SEARCH_RADIUS) {
+                Some(goal) if goal != start => return Some(goal),
+                _ => continue,
+            }
+        }
+
+        None
+    }
+}
+
+mod test {
+    use std::time::Duration;
+
+    use crate::gangsta::{map_registry::MapRegistry, vector::Vector, GameState};
+
+    use super::DESPAWN_DISTANCE;
+
+    #[test]
+    fn test() {
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let mut state = GameState::new(9, map);
+        state.traffic.density = 3;
+        for _ in 0..40 {
+            state.step(Duration::from_millis(50));
+        }
+        let spawned: Vec<String> = state.traffic.vehicles().cloned().collect();
+        assert!(!spawned.is_empty());
+        // tim and bob stand together, so they share the same cars
+        assert!(spawned.len() <= 3);
+        for id in &spawned {
+            let car = state.vehicle(id).unwrap();
+            assert!(car.npc_driver);
+            assert!(!car.path.is_empty());
+        }
+
+        // everyone leaves, the old cars are retired
+        let far = Vector::new(DESPAWN_DISTANCE * 3.0, DESPAWN_DISTANCE * 3.0);
+        for player in state.players.values_mut() {
+            player.set_position(far);
+        }
+        state.step(Duration::from_millis(50));
+        assert!(spawned.iter().all(|id| state.vehicle(id).is_none()));
+    }
+}
@@ -1,44 +1,223 @@
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::{
+    map::{tile_to_pixel, Coordinates, Intersection},
+    vector::Vector,
+    GameState,
+};
+
+const GREEN_TIME: Duration = Duration::from_secs(10);
+const YELLOW_TIME: Duration = Duration::from_secs(3);
+// both ways stay red for a moment so the junction can clear
+const ALL_RED_TIME: Duration = Duration::from_secs(1);
+// how far ahead of its bumper an AI car looks for a light
+pub const STOP_LOOKAHEAD: f32 = 96.0;
+// cars wait this far back from the junction
+pub const STOP_MARGIN: f32 = 4.0;
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum TrafficLightState {
     Green,
     Yellow,
     Red,
 }
 
-#[derive(Debug)]
+#[derive(Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    NorthSouth,
+    EastWest,
+}
+
+impl Axis {
+    pub fn of(direction: Vector) -> Self {
+        if direction.x.abs() >= direction.y.abs() {
+            Axis::EastWest
+        } else {
+            Axis::NorthSouth
+        }
+    }
+}
+
+// the lights for one intersection, north-south and east-west take turns
+#[derive(Debug, Clone)]
 pub struct TrafficLight {
-    pub state: TrafficLightState,
-    last_changed: Instant,
-    cycle_duration: Duration, // Duration of each cycle
+    pub id: String,
+    // pixel bounds of the junction
+    pub min: Vector,
+    pub max: Vector,
+    elapsed: Duration,
+}
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone)]
+pub struct TrafficLightInfo {
+    pub id: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub north_south: TrafficLightState,
+    pub east_west: TrafficLightState,
 }
 
 impl TrafficLight {
-    pub fn new() -> Self {
-        Self {
-            state: TrafficLightState::Green,
-            last_changed: Instant::now(),
-            cycle_duration: Duration::from_secs(10), // Change every 10 seconds
+    // `offset` is how far into its cycle the light starts, so neighbouring
+    // junctions don't all switch at once
+    pub fn new(id: String, intersection: &Intersection, offset: Duration) -> Self {
+        let max = Coordinates {
+            x: intersection.max.x + 1,
+            y: intersection.max.y + 1,
+        };
+        let mut light = Self {
+            id,
+            min: tile_to_pixel(intersection.min).into(),
+            max: tile_to_pixel(max).into(),
+            elapsed: Duration::ZERO,
+        };
+        light.update(offset);
+        light
+    }
+
+    pub fn cycle() -> Duration {
+        (GREEN_TIME + YELLOW_TIME + ALL_RED_TIME) * 2
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.elapsed += dt;
+        while self.elapsed >= Self::cycle() {
+            self.elapsed -= Self::cycle();
         }
     }
 
-    pub fn update(&mut self) {
-        let elapsed = self.last_changed.elapsed();
-        match self.state {
-            TrafficLightState::Green if elapsed >= self.cycle_duration => {
-                self.state = TrafficLightState::Yellow;
-                self.last_changed = Instant::now();
-            }
-            TrafficLightState::Yellow if elapsed >= Duration::from_secs(3) => {
-                self.state = TrafficLightState::Red;
-                self.last_changed = Instant::now();
-            }
-            TrafficLightState::Red if elapsed >= self.cycle_duration => {
-                self.state = TrafficLightState::Green;
-                self.last_changed = Instant::now();
+    pub fn state(&self, axis: Axis) -> TrafficLightState {
+        // east-west runs half a cycle behind north-south
+        let elapsed = match axis {
+            Axis::NorthSouth => self.elapsed,
+            Axis::EastWest if self.elapsed >= Self::cycle() / 2 => self.elapsed - Self::cycle() / 2,
+            Axis::EastWest => self.elapsed + Self::cycle() / 2,
+        };
+
+        if elapsed < GREEN_TIME {
+            TrafficLightState::Green
+        } else if elapsed < GREEN_TIME + YELLOW_TIME {
+            TrafficLightState::Yellow
+        } else {
+            TrafficLightState::Red
+        }
+    }
+
+    pub fn contains(&self, point: Vector) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    // distance along the ray until it enters the junction, if it does within `max`
+    pub fn distance_along(&self, origin: Vector, direction: Vector, max: f32) -> Option<f32> {
+        let mut near = 0.0_f32;
+        let mut far = max;
+        for (origin, direction, min, max) in [
+            (origin.x, direction.x, self.min.x, self.max.x),
+            (origin.y, direction.y, self.min.y, self.max.y),
+        ] {
+            if direction.abs() < f32::EPSILON {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
             }
-            _ => {}
+            let (a, b) = ((min - origin) / direction, (max - origin) / direction);
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+
+        (near <= far).then_some(near)
+    }
+
+    pub fn to_info(&self) -> TrafficLightInfo {
+        TrafficLightInfo {
+            id: self.id.clone(),
+            x: self.min.x,
+            y: self.min.y,
+            width: self.max.x - self.min.x,
+            height: self.max.y - self.min.y,
+            north_south: self.state(Axis::NorthSouth),
+            east_west: self.state(Axis::EastWest),
+        }
+    }
+}
+
+impl GameState {
+    pub(super) fn update_traffic_lights(&mut self, dt: Duration) {
+        for light in self.traffic_lights.iter_mut() {
+            light.update(dt);
         }
     }
 }
+
+// how far a car at `position` heading along `heading` can go before it has to
+// wait at a red or yellow light
+pub fn light_stop_distance(
+    lights: &[TrafficLight],
+    position: Vector,
+    heading: Vector,
+    half_length: f32,
+) -> Option<f32> {
+    let bumper = position + heading * half_length;
+    let axis = Axis::of(heading);
+    lights
+        .iter()
+        // a car already in the junction keeps going
+        .filter(|light| !light.contains(position))
+        .filter(|light| light.state(axis) != TrafficLightState::Green)
+        .filter_map(|light| light.distance_along(bumper, heading, STOP_LOOKAHEAD))
+        .map(|distance| (distance - STOP_MARGIN).max(0.0))
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+mod test {
+    use std::time::Duration;
+
+    use crate::gangsta::{
+        map::{Coordinates, Intersection},
+        vector::Vector,
+    };
+
+    use super::{Axis, TrafficLight, TrafficLightState, GREEN_TIME, YELLOW_TIME};
+
+    #[test]
+    fn test() {
+        let intersection = Intersection {
+            min: Coordinates { x: 2, y: 2 },
+            max: Coordinates { x: 4, y: 4 },
+        };
+        let mut light = TrafficLight::new("light".to_string(), &intersection, Duration::ZERO);
+        assert_eq!(light.state(Axis::NorthSouth), TrafficLightState::Green);
+        assert_eq!(light.state(Axis::EastWest), TrafficLightState::Red);
+
+        light.update(GREEN_TIME);
+        assert_eq!(light.state(Axis::NorthSouth), TrafficLightState::Yellow);
+        assert_eq!(light.state(Axis::EastWest), TrafficLightState::Red);
+        light.update(YELLOW_TIME * 2);
+        assert_eq!(light.state(Axis::NorthSouth), TrafficLightState::Red);
+        assert_eq!(light.state(Axis::EastWest), TrafficLightState::Green);
+
+        // the junction covers pixels 32 to 80
+        let east = Vector::new(1.0, 0.0);
+        assert_eq!(
+            light.distance_along(Vector::new(0.0, 40.0), east, 96.0),
+            Some(32.0)
+        );
+        assert_eq!(
+            light.distance_along(Vector::new(0.0, 100.0), east, 96.0),
+            None
+        );
+        assert_eq!(
+            light.distance_along(Vector::new(-100.0, 40.0), east, 96.0),
+            None
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    f32::consts::{PI, TAU},
+    ops::{Add, AddAssign, Mul, Sub, SubAssign},
+};
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::map::Coordinates;
+
+#[derive(Type, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+pub struct Vector {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vector {
+    pub const ZERO: Vector = Vector { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn from_angle(angle: f32) -> Self {
+        Self {
+            x: angle.cos(),
+            y: angle.sin(),
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.x.hypot(self.y)
+    }
+
+    pub fn dot(&self, other: Vector) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn to_coordinates(&self) -> Coordinates {
+        Coordinates {
+            x: self.x.round() as i32,
+            y: self.y.round() as i32,
+        }
+    }
+
+    pub fn normalized(&self) -> Vector {
+        let length = self.length();
+        if length == 0.0 {
+            Vector::ZERO
+        } else {
+            *self * (1.0 / length)
+        }
+    }
+}
+
+impl From<Coordinates> for Vector {
+    fn from(value: Coordinates) -> Self {
+        Vector::new(value.x as f32, value.y as f32)
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, rhs: Vector) -> Vector {
+        Vector::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl AddAssign for Vector {
+    fn add_assign(&mut self, rhs: Vector) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+    }
+}
+
+impl Sub for Vector {
+    type Output = Vector;
+
+    fn sub(self, rhs: Vector) -> Vector {
+        Vector::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl SubAssign for Vector {
+    fn sub_assign(&mut self, rhs: Vector) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+    }
+}
+
+impl Mul<f32> for Vector {
+    type Output = Vector;
+
+    fn mul(self, rhs: f32) -> Vector {
+        Vector::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+// wraps an angle in radians into [-PI, PI), in one step however far out it is
+pub fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
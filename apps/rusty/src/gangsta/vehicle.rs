@@ -1,15 +1,132 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 use std::collections::VecDeque;
-use std::future::Future;
-use std::pin::Pin;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::time::Duration;
 
 use super::{
-    action::{ActionBuilder, ActionTriggerType},
-    map::{tile_to_pixel, Coordinates},
-    CarDetails, CarSkin, GameObjectInfo, OutgoingGameObject,
+    action::use_object_trigger,
+    map::{tile_center, Coordinates},
+    vector::{wrap_angle, Vector},
+    CarDetails, CarSkin, Controllable, GameObjectInfo, OutgoingGameObject,
 };
-use crate::gangsta::GameObjectType;
+
+const WAYPOINT_RADIUS: f32 = 8.0;
+// below this the car counts as stopped, so holding brake starts reversing
+const STOPPED_SPEED: f32 = 5.0;
+// health lost per second once the car is on fire, it will blow up on its own
+const BURN_RATE: f32 = 4.0;
+
+#[derive(Type, Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct DriveControls {
+    pub throttle: f32,
+    pub brake: f32,
+    pub steer: f32,
+}
+
+impl DriveControls {
+    pub fn clamped(&self) -> Self {
+        Self {
+            throttle: self.throttle.clamp(0.0, 1.0),
+            brake: self.brake.clamp(0.0, 1.0),
+            steer: self.steer.clamp(-1.0, 1.0),
+        }
+    }
+}
+
+// all distances in pixels, speeds in pixels per second, angles in radians
+#[derive(Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct VehicleParams {
+    pub max_speed: f32,
+    pub max_reverse_speed: f32,
+    pub acceleration: f32,
+    pub reverse_acceleration: f32,
+    pub braking: f32,
+    // fraction of speed lost per second, so top speed is reached smoothly
+    pub drag: f32,
+    // constant deceleration from the tyres, stops a coasting car
+    pub rolling_resistance: f32,
+    pub wheelbase: f32,
+    pub max_steer_angle: f32,
+    // speed at which the usable steering angle has halved
+    pub steer_falloff_speed: f32,
+    pub length: f32,
+    pub width: f32,
+    pub mass: f32,
+    pub max_health: f32,
+    // seconds it takes to drag an NPC driver out
+    pub pull_out_time: f32,
+}
+
+impl VehicleParams {
+    pub fn for_skin(skin: &CarSkin) -> Self {
+        match skin {
+            CarSkin::Sedan => Self {
+                max_speed: 220.0,
+                max_reverse_speed: 60.0,
+                acceleration: 120.0,
+                reverse_acceleration: 80.0,
+                braking: 300.0,
+                drag: 0.3,
+                rolling_resistance: 20.0,
+                wheelbase: 20.0,
+                max_steer_angle: 0.6,
+                steer_falloff_speed: 150.0,
+                length: 28.0,
+                width: 14.0,
+                mass: 1200.0,
+                max_health: 100.0,
+                pull_out_time: 1.5,
+            },
+            CarSkin::Police => Self {
+                max_speed: 280.0,
+                max_reverse_speed: 70.0,
+                acceleration: 160.0,
+                reverse_acceleration: 90.0,
+                braking: 360.0,
+                drag: 0.3,
+                rolling_resistance: 20.0,
+                wheelbase: 22.0,
+                max_steer_angle: 0.6,
+                steer_falloff_speed: 180.0,
+                length: 30.0,
+                width: 14.0,
+                mass: 1400.0,
+                max_health: 150.0,
+                pull_out_time: 2.5,
+            },
+        }
+    }
+
+    // yaw rate at full lock while crawling, what the client uses to predict turns
+    pub fn max_turn_rate(&self) -> f32 {
+        STOPPED_SPEED.max(self.max_speed * 0.25) / self.wheelbase * self.max_steer_angle.tan()
+    }
+}
+
+#[derive(Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum DamageState {
+    Intact,
+    Dented,
+    // half engine power
+    Smoking,
+    // engine is dead and the fire keeps eating health
+    Burning,
+    Wrecked,
+}
+
+#[derive(Type, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Seat {
+    Driver,
+    Passenger,
+}
+
+#[derive(Type, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum VehicleOwner {
+    Player { user_id: String },
+    // traffic and police, anyone taking these is stealing
+    Npc,
+}
 
 #[derive(Type, Deserialize, Serialize, Debug, Clone)]
 pub enum VehicleBehavior {
@@ -18,163 +135,409 @@ pub enum VehicleBehavior {
     Aggressive,
 }
 
+impl VehicleBehavior {
+    // how close to the car's top speed the AI is willing to drive
+    pub fn speed_factor(&self) -> f32 {
+        match self {
+            VehicleBehavior::Cautious => 0.5,
+            VehicleBehavior::Normal => 0.75,
+            VehicleBehavior::Aggressive => 1.0,
+        }
+    }
+
+    // gap in pixels the AI leaves to whatever is in front of it
+    pub fn following_distance(&self) -> f32 {
+        match self {
+            VehicleBehavior::Cautious => 40.0,
+            VehicleBehavior::Normal => 24.0,
+            VehicleBehavior::Aggressive => 12.0,
+        }
+    }
+}
+
 #[derive(Type, Deserialize, Serialize, Debug, Clone)]
 pub struct Vehicle {
     pub id: String,
-    pub position: Coordinates,
+    pub position: Vector,
+    pub heading: f32,
+    pub speed: f32,
     pub behavior: VehicleBehavior,
-    pub path: VecDeque<Coordinates>,
+    pub path: VecDeque<Vector>,
     pub skin: CarSkin,
-    pub current_speed: u16,
-    pub acceleration: u16,
-    pub max_speed: u16,
-    pub rotation_speed: u16,
+    pub params: VehicleParams,
+    pub owner: VehicleOwner,
+    // the last player to steal it, getting back in isn't another theft
+    pub stolen_by: Option<String>,
+    // an NPC at the wheel drives the AI path until a player pulls them out
+    pub npc_driver: bool,
+    // how far the AI has left before it must stand still, e.g. at a red light
+    pub stop_distance: Option<f32>,
+    // limit of the road under the car, set each tick for AI drivers
+    pub speed_limit: Option<f32>,
     pub driver_user_id: Option<String>,
     pub passenger_user_ids: Vec<String>,
-    pub rotation: f32,
-    pub velocity: Coordinates,
+    pub max_passengers: u8,
+    pub velocity: Vector,
+    pub angular_velocity: f32,
+    pub controls: DriveControls,
+    pub health: f32,
+    // whoever last damaged the car gets the credit when it blows up
+    pub last_damaged_by: Option<String>,
+    pub wrecked_at: Option<Duration>,
 }
 
 impl Vehicle {
-    fn behavior_params(behavior: &VehicleBehavior) -> (u16, u16) {
-        match behavior {
-            VehicleBehavior::Cautious => (0, 2),
-            VehicleBehavior::Normal => (1, 100),
-            VehicleBehavior::Aggressive => (1, 120),
-        }
-    }
-
-    pub fn new(
-        id: String,
-        start: Coordinates,
-        behavior: VehicleBehavior,
-        rotation_speed: u16,
-    ) -> Self {
-        let (acceleration, max_speed) = Self::behavior_params(&behavior);
+    pub fn new(id: String, start: Vector, behavior: VehicleBehavior, skin: CarSkin) -> Self {
+        let params = VehicleParams::for_skin(&skin);
         Self {
             id,
             position: start,
+            heading: 0.0,
+            speed: 0.0,
             behavior,
-            rotation_speed,
+            path: VecDeque::new(),
+            params,
+            owner: VehicleOwner::Npc,
+            stolen_by: None,
+            npc_driver: true,
+            stop_distance: None,
+            speed_limit: None,
             driver_user_id: None,
             passenger_user_ids: vec![],
-            skin: CarSkin::Sedan,
-            velocity: Coordinates { x: 0, y: 0 },
-            rotation: 90.0,
-
-            current_speed: 0,
-            acceleration,
-            max_speed,
-            path: VecDeque::new(),
+            max_passengers: skin.max_passengers(),
+            skin,
+            velocity: Vector::ZERO,
+            angular_velocity: 0.0,
+            controls: DriveControls::default(),
+            health: params.max_health,
+            last_damaged_by: None,
+            wrecked_at: None,
         }
     }
 
     pub fn move_step(&mut self) {
-        println!("Remaining path: {:?}", self.path);
         if let Some(next_position) = self.path.pop_front() {
             self.position = next_position;
         }
     }
 
-    pub fn set_path(&mut self, path: Vec<Coordinates>) -> &Vehicle {
+    pub fn set_path(&mut self, path: Vec<Vector>) -> &Vehicle {
         self.path = VecDeque::from(path);
         self
     }
 
-    pub fn tick(&mut self) {
-        self.update_position();
+    pub fn tick(&mut self, dt: Duration) {
+        if self.is_wrecked() {
+            self.controls = DriveControls {
+                brake: 1.0,
+                ..Default::default()
+            };
+        } else if self.driver_user_id.is_none() && self.npc_driver {
+            self.controls = self.ai_controls();
+        }
+        if self.damage_state() == DamageState::Burning {
+            self.health = (self.health - BURN_RATE * dt.as_secs_f32()).max(0.0);
+        }
+        self.update_position(dt);
+    }
+
+    pub fn damage_state(&self) -> DamageState {
+        let fraction = self.health / self.params.max_health;
+        if self.is_wrecked() || self.health <= 0.0 {
+            DamageState::Wrecked
+        } else if fraction < 0.15 {
+            DamageState::Burning
+        } else if fraction < 0.4 {
+            DamageState::Smoking
+        } else if fraction < 0.75 {
+            DamageState::Dented
+        } else {
+            DamageState::Intact
+        }
+    }
+
+    pub fn is_owned_by(&self, user_id: &str) -> bool {
+        matches!(&self.owner, VehicleOwner::Player { user_id: owner } if owner == user_id)
+    }
+
+    // the owner, or whoever already stole it
+    pub fn has_keys(&self, user_id: &str) -> bool {
+        self.is_owned_by(user_id) || self.stolen_by.as_deref() == Some(user_id)
+    }
+
+    pub fn is_wrecked(&self) -> bool {
+        self.wrecked_at.is_some()
+    }
+
+    pub fn take_damage(&mut self, amount: f32, attacker: Option<String>) {
+        if amount <= 0.0 || self.is_wrecked() {
+            return;
+        }
+        self.health = (self.health - amount).max(0.0);
+        if attacker.is_some() {
+            self.last_damaged_by = attacker;
+        }
+    }
+
+    fn engine_power(&self) -> f32 {
+        match self.damage_state() {
+            DamageState::Intact | DamageState::Dented => 1.0,
+            DamageState::Smoking => 0.5,
+            DamageState::Burning | DamageState::Wrecked => 0.0,
+        }
     }
 
     pub fn to_outgoing_game_object(&self) -> OutgoingGameObject {
         OutgoingGameObject {
-            action: Some(
-                ActionBuilder::new(ActionTriggerType::ActionKeyPressed(32))
-                    .closure_action(
-                        |state, object_id, user_id| -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> {
-                            Box::pin(async move {
-                                println!("user {} wants to do something with the car {}!", user_id, object_id);
-                                if let Some(obj) = state.lock().await.objects.get_mut(&object_id) {
-                                    if let GameObjectType::Car(car_details) = &mut obj.details {
-                                        car_details.action(user_id);
-                                    }
-                                }
-                                Ok(())
-                            })
-                        },
-                    )
-                    .build(),
-            ),
+            action: (!self.is_wrecked()).then(use_object_trigger),
             controller_user_id: self.driver_user_id.clone(),
             id: self.id.clone(),
             x: self.position.x,
             y: self.position.y,
-            rotation: self.rotation,
-            velocity: self.velocity.clone(),
-            owner_user_id: self.id.clone(),
+            rotation: self.rotation(),
+            velocity: self.velocity,
+            angular_velocity: self.angular_velocity,
+            owner_user_id: match &self.owner {
+                VehicleOwner::Player { user_id } => Some(user_id.clone()),
+                VehicleOwner::Npc => None,
+            },
             details: GameObjectInfo::Car(CarDetails {
                 skin: self.skin.clone(),
-                speed: (self.current_speed * 128) as u16,
-                acceleration: self.acceleration.clone(),
-                max_passengers: self.passenger_user_ids.len() as u8,
+                speed: self.params.max_speed,
+                current_speed: self.speed,
+                acceleration: self.params.acceleration,
+                max_passengers: self.max_passengers,
                 passenger_user_ids: self.passenger_user_ids.clone(),
-                rotation_speed: self.rotation_speed,
+                rotation_speed: self.params.max_turn_rate(),
                 driver_user_id: self.driver_user_id.clone(),
+                health: self.health,
+                max_health: self.params.max_health,
+                damage: self.damage_state(),
             }),
         }
     }
 
-    pub fn action(&mut self, user_id: String) {
-        self.driver_user_id = Some(user_id);
+    pub fn is_occupant(&self, user_id: &str) -> bool {
+        self.seat_index(user_id).is_some()
+    }
+
+    // 0 is the driver, passengers follow in the order they got in
+    pub fn seat_index(&self, user_id: &str) -> Option<usize> {
+        if self.driver_user_id.as_deref() == Some(user_id) {
+            return Some(0);
+        }
+        self.passenger_user_ids
+            .iter()
+            .position(|id| id == user_id)
+            .map(|index| index + 1)
+    }
+
+    pub fn has_free_passenger_seat(&self) -> bool {
+        self.passenger_user_ids.len() < self.max_passengers as usize
+    }
+
+    pub fn remove_occupant(&mut self, user_id: &str) {
+        if self.driver_user_id.as_deref() == Some(user_id) {
+            self.release_control();
+        }
+        self.passenger_user_ids.retain(|id| id != user_id);
+    }
+
+    pub fn heading(&self) -> Vector {
+        Vector::from_angle(self.heading)
+    }
+
+    // the sprite faces up, so it is a quarter turn ahead of the heading
+    pub fn rotation(&self) -> f32 {
+        wrap_angle(self.heading + FRAC_PI_2)
     }
 
     pub fn set_tile_path(&mut self, tile_path: Vec<Coordinates>) {
-        let pixel_path = tile_path
-            .into_iter()
-            .map(|tile| tile_to_pixel(tile))
-            .collect();
+        let pixel_path = tile_path.into_iter().map(tile_center).collect();
         self.set_path(pixel_path);
     }
 
-    pub fn update_position(&mut self) {
-        if let Some(target) = self.path.front() {
-            if self.current_speed < self.max_speed {
-                self.current_speed += self.acceleration;
-                if self.current_speed > self.max_speed {
-                    self.current_speed = self.max_speed;
-                }
-            }
+    // steers for the next waypoint at the speed the behaviour allows, easing
+    // off for sharp turns and the end of the path
+    fn ai_controls(&mut self) -> DriveControls {
+        while self
+            .path
+            .front()
+            .is_some_and(|waypoint| (*waypoint - self.position).length() < WAYPOINT_RADIUS)
+        {
+            self.path.pop_front();
+        }
 
-            let dx = target.x - self.position.x;
-            let dy = target.y - self.position.y;
-            let distance = ((dx * dx + dy * dy) as f64).sqrt();
+        let Some(target) = self.path.front().copied() else {
+            return DriveControls {
+                brake: 1.0,
+                ..Default::default()
+            };
+        };
 
-            let desired_angle = (dy as f64).atan2(dx as f64) + std::f64::consts::FRAC_PI_2;
+        let to_target = target - self.position;
+        let angle = wrap_angle(to_target.y.atan2(to_target.x) - self.heading);
+        let steer = (angle / self.params.max_steer_angle).clamp(-1.0, 1.0);
 
-            let current_angle = self.rotation as f64;
-            let mut angle_diff = desired_angle - current_angle;
+        // aggressive drivers go right up to the limit, the rest stay under it
+        let limit = self.speed_limit.unwrap_or(f32::MAX);
+        let mut target_speed = self.params.max_speed.min(limit) * self.behavior.speed_factor();
+        target_speed *= (1.0 - angle.abs() / PI).max(0.2);
+        if self.path.len() == 1 {
+            target_speed = target_speed.min(to_target.length() * 2.0);
+        }
+        if let Some(stop) = self.stop_distance {
+            target_speed = target_speed.min(stop * 2.0);
+        }
 
-            while angle_diff < -std::f64::consts::PI {
-                angle_diff += 2.0 * std::f64::consts::PI;
-            }
-            while angle_diff > std::f64::consts::PI {
-                angle_diff -= 2.0 * std::f64::consts::PI;
-            }
+        let error = target_speed - self.speed;
+        let response = self.params.max_speed * 0.2;
+        DriveControls {
+            throttle: (error / response).clamp(0.0, 1.0),
+            brake: (-error / response).clamp(0.0, 1.0),
+            steer,
+        }
+    }
 
-            let rotation_change = if angle_diff.abs() < self.rotation_speed as f64 {
-                angle_diff
-            } else {
-                self.rotation_speed as f64 * angle_diff.signum()
-            };
-            self.rotation = (current_angle + rotation_change) as f32;
-
-            if distance < self.current_speed as f64 {
-                self.position = self.path.pop_front().unwrap();
-            } else {
-                let norm_x = dx as f64 / distance;
-                let norm_y = dy as f64 / distance;
-                self.position.x += (norm_x * self.current_speed as f64).round() as i32;
-                self.position.y += (norm_y * self.current_speed as f64).round() as i32;
+    fn integrate_speed(&mut self, dt: f32) {
+        let params = self.params;
+        let controls = self.controls;
+        // a damaged engine still brakes fine, it just can't pull
+        let power = self.engine_power();
+
+        let mut acceleration = 0.0;
+        if self.speed > STOPPED_SPEED {
+            acceleration += controls.throttle * params.acceleration * power;
+            acceleration -= controls.brake * params.braking;
+        } else if self.speed < -STOPPED_SPEED {
+            // while reversing brake is the accelerator and throttle the brake
+            acceleration -= controls.brake * params.reverse_acceleration * power;
+            acceleration += controls.throttle * params.braking;
+        } else if controls.throttle > 0.0 {
+            acceleration += controls.throttle * params.acceleration * power;
+        } else if self.driver_user_id.is_some() {
+            // only players back up; the AI and parked cars just hold the brake
+            acceleration -= controls.brake * params.reverse_acceleration * power;
+        }
+        acceleration -= params.drag * self.speed;
+
+        let mut speed = self.speed + acceleration * dt;
+        let rolling = params.rolling_resistance * dt;
+        speed = if speed.abs() <= rolling {
+            0.0
+        } else {
+            speed - rolling * speed.signum()
+        };
+        // braking brings the car to a stop, it never flips straight into reverse
+        if speed * self.speed < 0.0 {
+            speed = 0.0;
+        }
+
+        self.speed = speed.clamp(-params.max_reverse_speed, params.max_speed);
+    }
+
+    // kinematic bicycle model: the usable steering angle shrinks with speed, so
+    // the turning radius (wheelbase / tan(angle)) grows the faster we go
+    pub fn update_position(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        if dt <= 0.0 {
+            return;
+        }
+
+        self.integrate_speed(dt);
+
+        let falloff = 1.0 / (1.0 + self.speed.abs() / self.params.steer_falloff_speed);
+        let steer_angle = self.controls.steer * self.params.max_steer_angle * falloff;
+        self.angular_velocity = self.speed / self.params.wheelbase * steer_angle.tan();
+        self.heading = wrap_angle(self.heading + self.angular_velocity * dt);
+
+        self.velocity = self.heading() * self.speed;
+        self.position += self.velocity * dt;
+    }
+}
+
+impl Controllable for Vehicle {
+    fn take_control(&mut self, user_id: &str) {
+        self.driver_user_id = Some(user_id.to_string());
+        self.controls = DriveControls::default();
+        self.path.clear();
+    }
+
+    fn release_control(&mut self) {
+        self.driver_user_id = None;
+        self.controls = DriveControls {
+            brake: 1.0,
+            ..Default::default()
+        };
+    }
+
+    fn control(&mut self, controls: DriveControls) {
+        self.controls = controls.clamped();
+    }
+}
+
+mod test {
+    use std::time::Duration;
+
+    use crate::gangsta::{vector::Vector, CarSkin, Controllable};
+
+    use super::{DriveControls, Vehicle, VehicleBehavior};
+
+    fn driven(controls: DriveControls) -> Vehicle {
+        let mut vehicle = Vehicle::new(
+            "car".to_string(),
+            Vector::ZERO,
+            VehicleBehavior::Normal,
+            CarSkin::Sedan,
+        );
+        vehicle.take_control("tim");
+        vehicle.control(controls);
+        vehicle
+    }
+
+    fn turn_rate_at(speed: f32) -> f32 {
+        let mut vehicle = driven(DriveControls {
+            steer: 1.0,
+            ..Default::default()
+        });
+        vehicle.speed = speed;
+        vehicle.update_position(Duration::from_millis(50));
+        vehicle.angular_velocity
+    }
+
+    #[test]
+    fn test() {
+        let dt = Duration::from_millis(50);
+        let mut vehicle = driven(DriveControls {
+            throttle: 1.0,
+            ..Default::default()
+        });
+        for _ in 0..200 {
+            vehicle.tick(dt);
+        }
+        assert!(vehicle.speed <= vehicle.params.max_speed);
+        assert!(vehicle.position.x > 0.0);
+
+        // holding the brake stops the car without flipping it into reverse
+        vehicle.control(DriveControls {
+            brake: 1.0,
+            ..Default::default()
+        });
+        let mut stopped_at = None;
+        for tick in 0..200 {
+            vehicle.tick(dt);
+            assert!(vehicle.speed >= 0.0 || stopped_at.is_some());
+            if vehicle.speed == 0.0 && stopped_at.is_none() {
+                stopped_at = Some(tick);
             }
         }
+        assert!(stopped_at.is_some());
+        // once stopped, keeping the brake held backs the car up
+        assert!(vehicle.speed < 0.0);
+
+        // the turning radius (speed / turn rate) grows with speed
+        let slow = 40.0 / turn_rate_at(40.0);
+        let fast = 200.0 / turn_rate_at(200.0);
+        assert!(fast > slow);
     }
 }
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::error::{AppError, AppResult};
+
+use super::inventory::{Inventory, ItemKind};
+
+#[derive(Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeaponKind {
+    Melee,
+    Pistol,
+    Rifle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeaponParams {
+    pub damage: f32,
+    // pixels
+    pub range: f32,
+    pub fire_interval: Duration,
+    // how much ammo fits in one inventory slot, None for weapons that don't use any
+    pub max_ammo: Option<u32>,
+}
+
+impl WeaponKind {
+    pub fn params(&self) -> WeaponParams {
+        match self {
+            WeaponKind::Melee => WeaponParams {
+                damage: 15.0,
+                range: 20.0,
+                fire_interval: Duration::from_millis(500),
+                max_ammo: None,
+            },
+            WeaponKind::Pistol => WeaponParams {
+                damage: 20.0,
+                range: 400.0,
+                fire_interval: Duration::from_millis(350),
+                max_ammo: Some(60),
+            },
+            WeaponKind::Rifle => WeaponParams {
+                damage: 34.0,
+                range: 700.0,
+                fire_interval: Duration::from_millis(120),
+                max_ammo: Some(150),
+            },
+        }
+    }
+}
+
+// the equipped weapon, the weapons and ammo themselves live in the inventory
+#[derive(Type, Serialize, Deserialize, Debug, Clone)]
+pub struct WeaponState {
+    pub kind: WeaponKind,
+    #[serde(skip)]
+    ready_at: Duration,
+}
+
+impl WeaponState {
+    pub fn new(kind: WeaponKind) -> Self {
+        Self {
+            kind,
+            ready_at: Duration::ZERO,
+        }
+    }
+
+    // swapping weapons keeps the cooldown, otherwise a swap before every shot
+    // would get around the fire rate
+    pub fn switch(&mut self, kind: WeaponKind) {
+        self.kind = kind;
+    }
+
+    // spends a round from the inventory and starts the cooldown, `now` is
+    // simulation time
+    pub fn try_fire(
+        &mut self,
+        now: Duration,
+        inventory: &mut Inventory,
+    ) -> AppResult<WeaponParams> {
+        let params = self.kind.params();
+        if now < self.ready_at {
+            return Err(AppError::BadRequest("Weapon is not ready".to_string()));
+        }
+        if params.max_ammo.is_some() && inventory.take(ItemKind::Ammo(self.kind), 1) == 0 {
+            return Err(AppError::BadRequest("Out of ammo".to_string()));
+        }
+
+        self.ready_at = now + params.fire_interval;
+        Ok(params)
+    }
+}
+
+// players spawn holding their pistol
+impl Default for WeaponState {
+    fn default() -> Self {
+        Self::new(WeaponKind::Pistol)
+    }
+}
+
+mod test {
+    use std::time::Duration;
+
+    use crate::gangsta::inventory::{Inventory, ItemKind, ItemStack};
+
+    use super::{WeaponKind, WeaponState};
+
+    #[test]
+    fn test() {
+        let mut inventory = Inventory::default();
+        inventory.add(ItemStack::new(ItemKind::Ammo(WeaponKind::Pistol), 2));
+        let mut pistol = WeaponState::new(WeaponKind::Pistol);
+        let interval = WeaponKind::Pistol.params().fire_interval;
+
+        assert!(pistol.try_fire(Duration::ZERO, &mut inventory).is_ok());
+        assert!(pistol.try_fire(interval / 2, &mut inventory).is_err());
+        assert!(pistol.try_fire(interval, &mut inventory).is_ok());
+        assert_eq!(inventory.count(ItemKind::Ammo(WeaponKind::Pistol)), 0);
+        assert!(pistol.try_fire(interval * 3, &mut inventory).is_err());
+
+        let mut fists = WeaponState::new(WeaponKind::Melee);
+        for shot in 0..10 {
+            assert!(fists
+                .try_fire(Duration::from_secs(shot), &mut inventory)
+                .is_ok());
+        }
+    }
+}
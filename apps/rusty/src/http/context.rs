@@ -5,7 +5,9 @@ use sqlx::{Pool, Postgres};
 
 use crate::{
     error::{AppError, AppResult},
+    gangsta::map_registry::MapRegistry,
     lobby::manager::LobbyManager,
+    replay::manager::ReplayManager,
     services::jwt::{Claims, JwtService},
 };
 
@@ -14,10 +16,18 @@ pub struct Ctx {
     pub pool: Arc<Pool<Postgres>>,
     user: Option<Claims>,
     pub lobby_manager: Arc<LobbyManager>,
+    pub replay_manager: Arc<ReplayManager>,
+    pub map_registry: Arc<MapRegistry>,
 }
 
 impl Ctx {
-    pub fn new(pool: Arc<Pool<Postgres>>, parts: Parts, lobby_manager: Arc<LobbyManager>) -> Ctx {
+    pub fn new(
+        pool: Arc<Pool<Postgres>>,
+        parts: Parts,
+        lobby_manager: Arc<LobbyManager>,
+        replay_manager: Arc<ReplayManager>,
+        map_registry: Arc<MapRegistry>,
+    ) -> Ctx {
         // println!("{:?}", parts.headers);
         let user = match parts.headers.get("Authorization") {
             Some(bearer) => JwtService::decode(bearer.to_str().unwrap_or_default())
@@ -30,6 +40,8 @@ impl Ctx {
             pool,
             user,
             lobby_manager,
+            replay_manager,
+            map_registry,
         }
     }
 
@@ -2,24 +2,29 @@ use std::{
     collections::HashMap,
     sync::Arc,
     thread::{sleep, Thread},
-    time::Duration,
 };
 
 use async_stream::stream;
 use futures::{pin_mut, Stream};
 use serde::{Deserialize, Serialize};
 use specta::Type;
-use tokio::{
-    sync::{Mutex, MutexGuard},
-    time::interval,
-};
+use tokio::sync::{Mutex, MutexGuard};
 use tokio_stream::StreamExt;
 
 use crate::{
     error::{AppError, AppResult},
-    gangsta::{map::Coordinates, GameObjectType, OutgoingGameObject, PlayerInput},
+    gangsta::{
+        events::{GameEvent, KillFeedEntry},
+        inventory::{Inventory, InventoryAction},
+        map::Coordinates,
+        traffic_light::TrafficLightInfo,
+        vector::Vector,
+        vehicle::{DriveControls, Seat},
+        GameObjectType, GameState, OutgoingGameObject, PlayerInput,
+    },
     http::context::Ctx,
     lobby::{
+        game_loop::{GameLoop, GameLoopConfig},
         lobby::{Lobby, LobbyChat, LobbyData},
         manager::LobbyManager,
     },
@@ -28,14 +33,25 @@ use crate::{
 
 #[derive(Type, Serialize, Deserialize, Debug)]
 pub struct PersonalizedGameData {
+    tick: u32,
+    timestamp: f64,
     visible_objects: HashMap<String, OutgoingGameObject>,
+    events: Vec<GameEvent>,
+    kill_feed: Vec<KillFeedEntry>,
+    // only ever the receiving player's own
+    inventory: Option<Inventory>,
+    traffic_lights: Vec<TrafficLightInfo>,
 }
 
 impl PersonalizedGameData {
     pub async fn new(command: &LobbyData, user_id: &str) -> PersonalizedGameData {
+        let game = command.game.get_state().lock().await;
+        Self::from_state(&game, user_id)
+    }
+
+    pub fn from_state(game: &GameState, user_id: &str) -> PersonalizedGameData {
         let visible_objects = {
             let mut visible_objects = HashMap::new();
-            let game = command.game.get_state().lock().await;
             for (object_id, obj) in game.players.iter() {
                 visible_objects.insert(object_id.clone(), obj.to_outgoing_game_object());
             }
@@ -44,13 +60,42 @@ impl PersonalizedGameData {
                     GameObjectType::Car(car) => {
                         visible_objects.insert(object_id.clone(), car.to_outgoing_game_object());
                     }
+                    GameObjectType::Pedestrian(pedestrian) => {
+                        visible_objects
+                            .insert(object_id.clone(), pedestrian.to_outgoing_game_object());
+                    }
+                    GameObjectType::Item(item) => {
+                        visible_objects.insert(object_id.clone(), item.to_outgoing_game_object());
+                    }
                 }
             }
 
             visible_objects
         };
 
-        PersonalizedGameData { visible_objects }
+        PersonalizedGameData {
+            tick: game.tick,
+            timestamp: game.time.as_secs_f64() * 1000.0,
+            visible_objects,
+            events: match game.players.get(user_id) {
+                Some(player) => game
+                    .events
+                    .visible_from(player.position())
+                    .cloned()
+                    .collect(),
+                None => game.events.recent().cloned().collect(),
+            },
+            kill_feed: game.events.kill_feed().cloned().collect(),
+            inventory: game
+                .players
+                .get(user_id)
+                .map(|player| player.inventory.clone()),
+            traffic_lights: game
+                .traffic_lights
+                .iter()
+                .map(|light| light.to_info())
+                .collect(),
+        }
     }
 }
 
@@ -61,6 +106,35 @@ pub struct LobbyActionArgs {
     access_token: String,
     lobby_id: String,
     pub action_id: String,
+    pub client_tick: Option<u32>,
+}
+
+#[derive(Type, Deserialize, Debug)]
+pub struct LobbySeatArgs {
+    access_token: String,
+    lobby_id: String,
+    pub seat: Seat,
+}
+
+#[derive(Type, Deserialize, Debug)]
+pub struct LobbyFireArgs {
+    access_token: String,
+    lobby_id: String,
+    pub aim: Vector,
+    pub client_tick: Option<u32>,
+}
+
+#[derive(Type, Deserialize, Debug)]
+pub struct LobbyInventoryArgs {
+    access_token: String,
+    lobby_id: String,
+    pub action: InventoryAction,
+}
+
+#[derive(Type, Deserialize, Debug)]
+pub struct LobbyCreateArgs {
+    // the default map when left out
+    pub map: Option<String>,
 }
 
 #[derive(Type, Deserialize, Debug)]
@@ -70,6 +144,7 @@ pub struct LobbyInputArgs {
     pub r: f32,
     pub x: i32,
     pub y: i32,
+    pub controls: Option<DriveControls>,
 }
 
 impl LobbyController {
@@ -89,33 +164,41 @@ impl LobbyController {
         Ok(())
     }
 
-    pub async fn create(ctx: Ctx) -> AppResult<LobbyData> {
+    pub async fn create(ctx: Ctx, args: LobbyCreateArgs) -> AppResult<LobbyData> {
         let user = ctx.required_user()?;
-        let code = ctx.lobby_manager.create_lobby(user).await?;
+        let map = match &args.map {
+            Some(name) => ctx.map_registry.get(name)?,
+            None => ctx.map_registry.default_map()?,
+        };
+        let code = ctx.lobby_manager.create_lobby(user, map).await?;
         let lobby = ctx
             .lobby_manager
             .get_lobby(&code)
             .await
             .map_err(|x| AppError::BadRequest("No such lobby".to_string()))?;
-        let data = lobby.lock().await.data.clone();
-
-        let lobby_clone = lobby.clone();
-        let code_clone = code.clone();
-        let lobby_manager = ctx.lobby_manager.clone();
-
-        tokio::spawn(async move {
-            let tick_duration = Duration::from_millis(50);
-            let mut ticker = interval(tick_duration);
-            loop {
-                ticker.tick().await;
-                {
-                    let mut lobby_guard = lobby_clone.lock().await;
-                    lobby_guard.data.game.tick().await;
-                }
+        let (data, game, loop_metrics) = {
+            let lobby = lobby.lock().await;
+            (
+                lobby.data.clone(),
+                lobby.data.game.clone(),
+                lobby.loop_metrics.clone(),
+            )
+        };
 
-                lobby_manager.notify_lobby(&code_clone).await.ok();
+        let config = GameLoopConfig::from_env();
+        if let Some(path) = ctx.replay_manager.recording_path(&code) {
+            if let Err(e) = game
+                .get_state()
+                .lock()
+                .await
+                .start_recording(&path, config.timestep)
+            {
+                eprintln!("Unable to record replay for lobby {}: {:?}", code, e);
             }
-        });
+        }
+
+        let game_loop = GameLoop::new(config, game, loop_metrics);
+        tokio::spawn(game_loop.run(ctx.lobby_manager.clone(), code));
 
         Ok(data)
     }
@@ -149,6 +232,7 @@ impl LobbyController {
                     rotation: args.r,
                     x: args.x,
                     y: args.y,
+                    controls: args.controls,
                 },
             )
             .await;
@@ -156,6 +240,63 @@ impl LobbyController {
         Ok(())
     }
 
+    pub(crate) async fn seat(ctx: Ctx, args: LobbySeatArgs) -> AppResult<()> {
+        let user_claims = JwtService::decode(&args.access_token)?.claims;
+        let lobby = ctx
+            .lobby_manager
+            .get_lobby(&args.lobby_id)
+            .await
+            .map_err(|_| AppError::BadRequest("Bad lobby id".to_string()))?;
+
+        lobby
+            .lock()
+            .await
+            .data
+            .game
+            .switch_seat(user_claims.sub, args.seat)
+            .await;
+
+        Ok(())
+    }
+
+    pub(crate) async fn fire(ctx: Ctx, args: LobbyFireArgs) -> AppResult<()> {
+        let user_claims = JwtService::decode(&args.access_token)?.claims;
+        let lobby = ctx
+            .lobby_manager
+            .get_lobby(&args.lobby_id)
+            .await
+            .map_err(|_| AppError::BadRequest("Bad lobby id".to_string()))?;
+
+        lobby
+            .lock()
+            .await
+            .data
+            .game
+            .fire(user_claims.sub, args.aim, args.client_tick)
+            .await;
+
+        Ok(())
+    }
+
+    pub(crate) async fn inventory(ctx: Ctx, args: LobbyInventoryArgs) -> AppResult<()> {
+        let user_claims = JwtService::decode(&args.access_token)?.claims;
+        let lobby = ctx
+            .lobby_manager
+            .get_lobby(&args.lobby_id)
+            .await
+            .map_err(|_| AppError::BadRequest("Bad lobby id".to_string()))?;
+
+        lobby
+            .lock()
+            .await
+            .data
+            .game
+            .inventory(user_claims.sub, args.action)
+            .await;
+
+        Ok(())
+    }
+
     pub(crate) async fn action(ctx: Ctx, args: LobbyActionArgs) -> AppResult<()> {
         let user_claims = JwtService::decode(&args.access_token).unwrap().claims;
         let lobby = ctx
@@ -169,7 +310,7 @@ impl LobbyController {
             .await
             .data
             .game
-            .action(user_claims.sub, args.action_id)
+            .action(user_claims.sub, args.action_id, args.client_tick)
             .await?;
 
         Ok(())
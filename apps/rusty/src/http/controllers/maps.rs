@@ -0,0 +1,9 @@
+use crate::{error::AppResult, gangsta::map_registry::MapInfo, http::context::Ctx};
+
+pub struct MapsController {}
+
+impl MapsController {
+    pub(crate) async fn list(ctx: Ctx) -> AppResult<Vec<MapInfo>> {
+        Ok(ctx.map_registry.list())
+    }
+}
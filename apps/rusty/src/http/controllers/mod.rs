@@ -1,2 +1,4 @@
 pub mod authentication;
 pub mod lobby;
+pub mod maps;
+pub mod replay;
@@ -0,0 +1,105 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::Stream;
+use serde::Deserialize;
+use specta::Type;
+use tokio::time::sleep;
+
+use crate::{
+    error::AppResult,
+    gangsta::{replay::ReplaySpeed, GameState},
+    http::{context::Ctx, controllers::lobby::PersonalizedGameData},
+    services::jwt::JwtService,
+};
+
+const FALLBACK_TIMESTEP: Duration = Duration::from_millis(50);
+
+pub struct ReplayController {}
+
+#[derive(Type, Deserialize, Debug)]
+pub struct ReplayWatchArgs {
+    access_token: String,
+    pub replay_id: String,
+    pub speed: ReplaySpeed,
+}
+
+#[derive(Type, Deserialize, Debug)]
+pub struct ReplaySpeedArgs {
+    access_token: String,
+    pub replay_id: String,
+    pub speed: ReplaySpeed,
+}
+
+impl ReplayController {
+    pub(crate) async fn list(ctx: Ctx) -> AppResult<Vec<String>> {
+        ctx.replay_manager.list()
+    }
+
+    pub(crate) async fn speed(ctx: Ctx, args: ReplaySpeedArgs) -> AppResult<()> {
+        let user_claims = JwtService::decode(&args.access_token)?.claims;
+        ctx.replay_manager
+            .set_speed(&user_claims.sub, &args.replay_id, args.speed)
+            .await
+    }
+
+    pub(crate) fn watch(
+        ctx: Ctx,
+        args: ReplayWatchArgs,
+    ) -> impl Stream<Item = PersonalizedGameData> + Send + 'static {
+        let manager = Arc::clone(&ctx.replay_manager);
+        let maps = Arc::clone(&ctx.map_registry);
+
+        async_stream::stream! {
+            let user_id = match JwtService::decode(&args.access_token) {
+                Ok(token) => token.claims.sub,
+                Err(e) => {
+                    eprintln!("Error watching replay: {:?}", e);
+                    return;
+                }
+            };
+            let replay = match manager.load(&args.replay_id) {
+                Ok(replay) => replay,
+                Err(e) => {
+                    eprintln!("Error loading replay {}: {:?}", args.replay_id, e);
+                    return;
+                }
+            };
+
+            let map = match maps.get(&replay.header.map) {
+                Ok(map) => map,
+                Err(_) => {
+                    eprintln!("Replay {} uses unknown map {}", args.replay_id, replay.header.map);
+                    return;
+                }
+            };
+            let mut state = GameState::new(replay.header.seed, map);
+
+            let timestep = if replay.log.timestep.is_zero() {
+                FALLBACK_TIMESTEP
+            } else {
+                replay.log.timestep
+            };
+            // dropped along with the stream, which closes the session
+            let (mut speed, _session) = manager
+                .open_session(&user_id, &args.replay_id, args.speed)
+                .await;
+
+            yield PersonalizedGameData::from_state(&state, &user_id);
+            loop {
+                let current = *speed.borrow_and_update();
+                if current == ReplaySpeed::Paused {
+                    if speed.changed().await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+
+                sleep(timestep / current.multiplier()).await;
+                if !state.step_from_log(&replay.log) {
+                    break;
+                }
+                yield PersonalizedGameData::from_state(&state, &user_id);
+            }
+        }
+    }
+}
@@ -14,7 +14,11 @@ use tokio_stream::wrappers::ReceiverStream;
 use crate::error::AppError;
 use crate::http::context::Ctx;
 use crate::http::controllers::lobby::LobbyActionArgs;
+use crate::http::controllers::lobby::LobbyCreateArgs;
+use crate::http::controllers::lobby::LobbyFireArgs;
 use crate::http::controllers::lobby::LobbyInputArgs;
+use crate::http::controllers::lobby::LobbyInventoryArgs;
+use crate::http::controllers::lobby::LobbySeatArgs;
 use crate::services::jwt::JwtService;
 use crate::{http::controllers::lobby::LobbyController, lobby::lobby::LobbyData};
 
@@ -32,11 +36,22 @@ pub fn create_lobby_router() -> rspc::RouterBuilder<Ctx> {
         .mutation("action", |t| {
             t(|ctx, args: LobbyActionArgs| async move { Ok(LobbyController::action(ctx, args).await?) })
         })
+        .mutation("fire", |t| {
+            t(|ctx, args: LobbyFireArgs| async move { Ok(LobbyController::fire(ctx, args).await?) })
+        })
+        .mutation("seat", |t| {
+            t(|ctx, args: LobbySeatArgs| async move { Ok(LobbyController::seat(ctx, args).await?) })
+        })
+        .mutation("inventory", |t| {
+            t(|ctx, args: LobbyInventoryArgs| async move {
+                Ok(LobbyController::inventory(ctx, args).await?)
+            })
+        })
         .mutation("input", |t| {
             t(|ctx, args: LobbyInputArgs| async move { Ok(LobbyController::input(ctx, args).await?) })
         })
         .mutation("create", |t| {
-            t(|ctx, _: Vec<String>| async move { Ok(LobbyController::create(ctx).await?) })
+            t(|ctx, args: LobbyCreateArgs| async move { Ok(LobbyController::create(ctx, args).await?) })
         })
         .subscription("subscribe", |t| {
             t(|ctx, (code, access_token): (String, String)| {
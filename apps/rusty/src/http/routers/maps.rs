@@ -0,0 +1,9 @@
+use rspc::Router;
+
+use crate::http::{context::Ctx, controllers::maps::MapsController};
+
+pub fn create_maps_router() -> rspc::RouterBuilder<Ctx> {
+    Router::<Ctx>::new().query("list", |t| {
+        t(|ctx, _: ()| async move { Ok(MapsController::list(ctx).await?) })
+    })
+}
@@ -2,17 +2,23 @@ use std::{path::PathBuf, sync::Arc};
 
 use authentication::create_authentication_router;
 use lobby::create_lobby_router;
+use maps::create_maps_router;
+use replay::create_replay_router;
 
 use super::context::Ctx;
 
 mod authentication;
 mod lobby;
+mod maps;
+mod replay;
 
 pub fn create_router() -> Arc<rspc::Router<Ctx>> {
     let router = rspc::Router::<Ctx>::new()
         .query("version", |t| t(|ctx, input: ()| env!("CARGO_PKG_VERSION")))
         .merge("authentication.", create_authentication_router())
         .merge("lobby.", create_lobby_router())
+        .merge("maps.", create_maps_router())
+        .merge("replay.", create_replay_router())
         .build()
         .arced();
 
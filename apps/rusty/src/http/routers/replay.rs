@@ -0,0 +1,23 @@
+use rspc::Router;
+
+use crate::http::{
+    context::Ctx,
+    controllers::replay::{ReplayController, ReplaySpeedArgs, ReplayWatchArgs},
+};
+
+pub fn create_replay_router() -> rspc::RouterBuilder<Ctx> {
+    Router::<Ctx>::new()
+        .query("list", |t| {
+            t(|ctx, _: ()| async move { Ok(ReplayController::list(ctx).await?) })
+        })
+        .mutation("speed", |t| {
+            t(
+                |ctx, args: ReplaySpeedArgs| async move {
+                    Ok(ReplayController::speed(ctx, args).await?)
+                },
+            )
+        })
+        .subscription("watch", |t| {
+            t(|ctx, args: ReplayWatchArgs| ReplayController::watch(ctx, args))
+        })
+}
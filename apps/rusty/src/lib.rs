@@ -4,4 +4,5 @@ pub mod gangsta;
 pub mod http;
 pub mod lobby;
 mod models;
+pub mod replay;
 pub mod services;
@@ -0,0 +1,146 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    sync::Mutex,
+    time::{interval, Instant, MissedTickBehavior},
+};
+
+use crate::gangsta::Game;
+
+use super::manager::LobbyManager;
+
+const DEFAULT_TICK_RATE: u32 = 20;
+const DEFAULT_MAX_STEPS_PER_FRAME: u32 = 5;
+const METRICS_LOG_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct GameLoopConfig {
+    pub timestep: Duration,
+    pub snapshot_interval: Duration,
+    pub max_steps_per_frame: u32,
+}
+
+impl GameLoopConfig {
+    pub fn new(tick_rate: u32, snapshot_rate: u32) -> Self {
+        Self {
+            timestep: Duration::from_secs(1) / tick_rate.max(1),
+            snapshot_interval: Duration::from_secs(1) / snapshot_rate.max(1),
+            max_steps_per_frame: DEFAULT_MAX_STEPS_PER_FRAME,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let rate = |key: &str| dotenv::var(key).ok().and_then(|v| v.parse::<u32>().ok());
+        let tick_rate = rate("GAME_TICK_RATE").unwrap_or(DEFAULT_TICK_RATE);
+        let snapshot_rate = rate("GAME_SNAPSHOT_RATE").unwrap_or(tick_rate);
+
+        Self::new(tick_rate, snapshot_rate)
+    }
+}
+
+impl Default for GameLoopConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_TICK_RATE, DEFAULT_TICK_RATE)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GameLoopMetrics {
+    pub ticks: u64,
+    pub snapshots: u64,
+    // simulation steps that took longer than the timestep to run
+    pub overruns: u64,
+    // steps thrown away because the loop fell more than max_steps_per_frame behind
+    pub dropped_steps: u64,
+    pub last_tick_duration: Duration,
+    pub max_tick_duration: Duration,
+}
+
+impl GameLoopMetrics {
+    fn record_tick(&mut self, elapsed: Duration, timestep: Duration) {
+        self.ticks += 1;
+        self.last_tick_duration = elapsed;
+        if elapsed > self.max_tick_duration {
+            self.max_tick_duration = elapsed;
+        }
+        if elapsed > timestep {
+            self.overruns += 1;
+        }
+    }
+}
+
+pub struct GameLoop {
+    config: GameLoopConfig,
+    game: Game,
+    metrics: Arc<Mutex<GameLoopMetrics>>,
+}
+
+impl GameLoop {
+    pub fn new(config: GameLoopConfig, game: Game, metrics: Arc<Mutex<GameLoopMetrics>>) -> Self {
+        Self {
+            config,
+            game,
+            metrics,
+        }
+    }
+
+    pub async fn run(mut self, lobby_manager: Arc<LobbyManager>, code: String) {
+        let timestep = self.config.timestep;
+        let mut ticker = interval(timestep);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut accumulator = Duration::ZERO;
+        let mut last_frame = Instant::now();
+        let mut last_snapshot = last_frame;
+        let mut last_metrics_log = last_frame;
+
+        loop {
+            ticker.tick().await;
+
+            let now = Instant::now();
+            accumulator += now - last_frame;
+            last_frame = now;
+
+            let mut steps = 0;
+            while accumulator >= timestep {
+                if steps == self.config.max_steps_per_frame {
+                    let behind = (accumulator.as_nanos() / timestep.as_nanos()) as u64;
+                    self.metrics.lock().await.dropped_steps += behind;
+                    accumulator = Duration::ZERO;
+                    break;
+                }
+
+                let started = Instant::now();
+                self.game.tick(timestep).await;
+                self.metrics
+                    .lock()
+                    .await
+                    .record_tick(started.elapsed(), timestep);
+
+                accumulator -= timestep;
+                steps += 1;
+            }
+
+            if steps > 0 && now - last_snapshot >= self.config.snapshot_interval {
+                last_snapshot = now;
+                if lobby_manager.notify_lobby(&code).await.is_ok() {
+                    self.metrics.lock().await.snapshots += 1;
+                }
+            }
+
+            if now - last_metrics_log >= METRICS_LOG_INTERVAL {
+                last_metrics_log = now;
+                let metrics = self.metrics.lock().await;
+                println!(
+                    "lobby {} game loop: {} ticks, {} snapshots, {} overruns, {} dropped steps, max tick {:?}",
+                    code,
+                    metrics.ticks,
+                    metrics.snapshots,
+                    metrics.overruns,
+                    metrics.dropped_steps,
+                    metrics.max_tick_duration
+                );
+            }
+        }
+    }
+}
@@ -16,32 +16,38 @@ impl LobbyChat {
     }
 }
 
-#[derive(Type, Deserialize, Serialize, Debug, Clone)]
+#[derive(Type, Serialize, Debug, Clone)]
 pub struct LobbyData {
     pub join_code: String,
     pub chat: Vec<LobbyChat>,
+    // chosen when the lobby is created, every client has to load the same one
+    pub map: String,
 
-    #[serde(skip_serializing, skip_deserializing)]
+    #[serde(skip)]
     pub game: Game,
 }
-impl Default for LobbyData {
-    fn default() -> LobbyData {
+impl LobbyData {
+    pub fn new(map: Arc<Map>) -> LobbyData {
         let code = ulid::Ulid::new().to_string();
         // game_state.code = code.clone();
 
         LobbyData {
             join_code: code,
             chat: vec![],
-            game: Game::default(),
+            map: map.name.clone(),
+            game: Game::new(map),
         }
     }
 }
 
-#[derive(Type, Deserialize, Serialize, Debug)]
+#[derive(Type, Serialize, Debug)]
 pub struct Lobby {
-    #[serde(skip_serializing, skip_deserializing)]
+    #[serde(skip)]
     pub pub_tx: Option<broadcast::Sender<LobbyData>>,
 
+    #[serde(skip)]
+    pub loop_metrics: Arc<Mutex<GameLoopMetrics>>,
+
     pub data: LobbyData,
 }
 
@@ -57,21 +63,23 @@ use crate::{
     error::{AppError, AppResult},
     gangsta::{
         action::{ActionBuilder, ActionTriggerType},
+        map::Map,
         CarDetails, CarSkin, Game, GameObjectInfo, PersonDetails,
     },
     http::controllers::lobby::LobbyInputArgs,
     services::jwt::Claims,
 };
 
-use super::manager::LobbyManager;
+use super::{game_loop::GameLoopMetrics, manager::LobbyManager};
 
 impl Lobby {
-    pub async fn new(user: &Claims) -> Self {
+    pub async fn new(user: &Claims, map: Arc<Map>) -> Self {
         let (pub_tx, _) = broadcast::channel(2048);
 
         let mut lobby = Lobby {
             pub_tx: Some(pub_tx),
-            data: LobbyData::default(),
+            loop_metrics: Arc::new(Mutex::new(GameLoopMetrics::default())),
+            data: LobbyData::new(map),
         };
 
         lobby.join(user).await;
@@ -103,7 +111,7 @@ mod test {
 
     use tokio_stream::StreamExt;
 
-    use crate::{lobby::lobby::Lobby, services::jwt::Claims};
+    use crate::{gangsta::map_registry::MapRegistry, lobby::lobby::Lobby, services::jwt::Claims};
 
     #[tokio::test]
     async fn test() {
@@ -117,7 +125,8 @@ mod test {
             jti: Some("asdkjfjskd".to_string()),
             exp: 0,
         };
-        let lobby = &Rc::new(RefCell::new(Lobby::new(&user_id).await));
+        let map = MapRegistry::bundled().default_map().unwrap();
+        let lobby = &Rc::new(RefCell::new(Lobby::new(&user_id, map).await));
 
         lobby
             .clone()
@@ -21,6 +21,7 @@ use tokio::sync::Mutex;
 
 use super::lobby::{Lobby, LobbyData};
 use crate::error::{AppError, AppResult};
+use crate::gangsta::map::Map;
 use crate::http::controllers::lobby::PersonalizedGameData;
 use crate::services::jwt::{Claims, JwtService};
 
@@ -75,9 +76,9 @@ impl std::fmt::Debug for LobbyManager {
 }
 
 impl LobbyManager {
-    pub async fn create_lobby(self: &Arc<Self>, user: &Claims) -> AppResult<String> {
+    pub async fn create_lobby(self: &Arc<Self>, user: &Claims, map: Arc<Map>) -> AppResult<String> {
         let mut lobbies = self.lobbies.lock().await;
-        let lobby = Lobby::new(user).await;
+        let lobby = Lobby::new(user, map).await;
         let lobby_id = lobby.data.join_code.clone();
         let lobby_manager_weak = Arc::downgrade(self);
         let lobby_id_clone = lobby_id.clone();
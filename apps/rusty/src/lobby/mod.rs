@@ -1,2 +1,3 @@
+pub mod game_loop;
 pub mod lobby;
 pub mod manager;
@@ -0,0 +1,167 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
+
+use tokio::sync::{watch, Mutex};
+
+use crate::{
+    error::{AppError, AppResult},
+    gangsta::replay::{replay_dir, replay_path, Replay, ReplaySpeed},
+};
+
+#[derive(Debug)]
+pub struct ReplayManager {
+    dir: Option<PathBuf>,
+    sessions: Mutex<HashMap<String, watch::Sender<ReplaySpeed>>>,
+}
+
+fn session_key(user_id: &str, replay_id: &str) -> String {
+    format!("{}:{}", user_id, replay_id)
+}
+
+// closes the session once the stream holding it goes away, whether the
+// replay ran to the end or the client disconnected
+#[derive(Debug)]
+pub struct SessionGuard {
+    manager: Arc<ReplayManager>,
+    user_id: String,
+    replay_id: String,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let manager = Arc::clone(&self.manager);
+        let (user_id, replay_id) = (self.user_id.clone(), self.replay_id.clone());
+        runtime.spawn(async move { manager.close_session(&user_id, &replay_id).await });
+    }
+}
+
+impl ReplayManager {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        if let Some(dir) = &dir {
+            if let Err(e) = fs::create_dir_all(dir) {
+                eprintln!("Unable to create replay directory {:?}: {:?}", dir, e);
+            }
+        }
+
+        Self {
+            dir,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(replay_dir())
+    }
+
+    // where a new game should record to, or None if recording is turned off
+    pub fn recording_path(&self, replay_id: &str) -> Option<PathBuf> {
+        replay_path(self.dir.as_ref()?, replay_id)
+    }
+
+    pub fn list(&self) -> AppResult<Vec<String>> {
+        let Some(dir) = &self.dir else {
+            return Ok(vec![]);
+        };
+
+        let mut replays: Vec<String> = fs::read_dir(dir)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "replay"))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect();
+        replays.sort();
+
+        Ok(replays)
+    }
+
+    pub fn load(&self, replay_id: &str) -> AppResult<Replay> {
+        let path = self
+            .recording_path(replay_id)
+            .ok_or(AppError::BadRequest("No such replay".to_string()))?;
+
+        Replay::load(&path).map_err(|e| AppError::BadRequest(e.to_string()))
+    }
+
+    pub async fn open_session(
+        self: &Arc<Self>,
+        user_id: &str,
+        replay_id: &str,
+        speed: ReplaySpeed,
+    ) -> (watch::Receiver<ReplaySpeed>, SessionGuard) {
+        let (tx, rx) = watch::channel(speed);
+        self.sessions
+            .lock()
+            .await
+            .insert(session_key(user_id, replay_id), tx);
+
+        let guard = SessionGuard {
+            manager: Arc::clone(self),
+            user_id: user_id.to_string(),
+            replay_id: replay_id.to_string(),
+        };
+        (rx, guard)
+    }
+
+    pub async fn set_speed(
+        &self,
+        user_id: &str,
+        replay_id: &str,
+        speed: ReplaySpeed,
+    ) -> AppResult<()> {
+        let mut sessions = self.sessions.lock().await;
+        let key = session_key(user_id, replay_id);
+        let tx = sessions
+            .get(&key)
+            .ok_or(AppError::BadRequest("Not watching this replay".to_string()))?;
+
+        if tx.send(speed).is_err() {
+            sessions.remove(&key);
+            return Err(AppError::BadRequest("Not watching this replay".to_string()));
+        }
+
+        Ok(())
+    }
+
+    // only once nobody is listening, so a newer session for the same replay
+    // is left alone
+    pub async fn close_session(&self, user_id: &str, replay_id: &str) {
+        let mut sessions = self.sessions.lock().await;
+        let key = session_key(user_id, replay_id);
+        if sessions.get(&key).is_some_and(|tx| tx.is_closed()) {
+            sessions.remove(&key);
+        }
+    }
+}
+
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::gangsta::replay::ReplaySpeed;
+
+    use super::ReplayManager;
+
+    #[tokio::test]
+    async fn test() {
+        let manager = Arc::new(ReplayManager::new(None));
+        let (speed, guard) = manager
+            .open_session("tim", "abc", ReplaySpeed::Paused)
+            .await;
+        manager
+            .set_speed("tim", "abc", ReplaySpeed::Double)
+            .await
+            .unwrap();
+        assert_eq!(*speed.borrow(), ReplaySpeed::Double);
+
+        // the client went away halfway through
+        drop((speed, guard));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(manager
+            .set_speed("tim", "abc", ReplaySpeed::Normal)
+            .await
+            .is_err());
+        assert!(manager.sessions.lock().await.is_empty());
+    }
+}